@@ -0,0 +1,174 @@
+use crate::configuration::ConfigurationSource;
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage: rust-kata-004 [OPTIONS] [COMMAND]
+
+Commands:
+  run                     Runs the operator (default)
+  check-config            Validates the configuration then exits
+
+Options:
+  --config-dir <DIR>      Directory of the configuration files [default: ./configuration]
+  --environment <NAMES>   Comma separated environment files to merge, later ones take
+                          precedence [default: $APP_ENVIRONMENT or local]
+  --overlay <FILE>        Merges the file after the environments if it exists, may be repeated
+  --set <KEY=VALUE>       Overrides a setting, may be repeated
+  --print-config          Prints the merged configuration with secrets redacted then exits
+  -h, --help              Prints this message then exits";
+
+/// Command line arguments of the operator.
+#[derive(Debug, Default, PartialEq)]
+pub struct Cli {
+    /// Directory of the configuration files, `--config-dir`.
+    pub config_dir: Option<PathBuf>,
+    /// Comma separated environment files to merge, `--environment`.
+    pub environment: Option<String>,
+    /// Optional files merged after the environments, `--overlay`.
+    pub overlays: Vec<PathBuf>,
+    /// Settings overriding the configuration, `--set key=value`.
+    pub overrides: Vec<(String, String)>,
+    /// Prints the merged configuration then exits, `--print-config`.
+    pub print_config: bool,
+    /// Prints the usage then exits, `--help`.
+    pub help: bool,
+    pub command: Command,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub enum Command {
+    #[default]
+    Run,
+    CheckConfig,
+}
+
+impl Cli {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--config-dir" => {
+                    parsed.config_dir = Some(PathBuf::from(value(&arg, args.next())?))
+                }
+                "--environment" => parsed.environment = Some(value(&arg, args.next())?),
+                "--overlay" => parsed
+                    .overlays
+                    .push(PathBuf::from(value(&arg, args.next())?)),
+                "--set" => {
+                    let setting = value(&arg, args.next())?;
+                    let (key, value) = split_setting(&setting)?;
+                    parsed.overrides.push((key, value));
+                }
+                "--print-config" => parsed.print_config = true,
+                "-h" | "--help" => parsed.help = true,
+                "run" => parsed.command = Command::Run,
+                "check-config" => parsed.command = Command::CheckConfig,
+                other => return Err(format!("Unrecognized argument '{}'.", other)),
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    /// Source of the configuration selected by the arguments.
+    pub fn source(&self) -> ConfigurationSource {
+        let mut source = ConfigurationSource::default();
+        if let Some(config_dir) = &self.config_dir {
+            source.directory = config_dir.clone();
+        }
+        source.environment = self.environment.clone();
+        source.overlays = self.overlays.clone();
+        source.overrides = self.overrides.clone();
+        source
+    }
+}
+
+fn value(option: &str, value: Option<String>) -> Result<String, String> {
+    value.ok_or_else(|| format!("Option '{}' with no value.", option))
+}
+
+fn split_setting(setting: &str) -> Result<(String, String), String> {
+    match setting.find('=') {
+        Some(index) if index > 0 => Ok((
+            setting[..index].to_string(),
+            setting[index + 1..].to_string(),
+        )),
+        _ => Err(format!(
+            "Option '--set' expects KEY=VALUE, got '{}'.",
+            setting
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Cli, String> {
+        Cli::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parse_defaults_to_run() {
+        let cli = parse(&[]).unwrap();
+
+        assert_eq!(Cli::default(), cli);
+        assert_eq!(Command::Run, cli.command);
+    }
+
+    #[test]
+    fn parse_options_and_command() {
+        let cli = parse(&[
+            "--config-dir",
+            "/etc/rust-kata-004",
+            "--environment",
+            "production,eu-west",
+            "--overlay",
+            "/etc/rust-kata-004/overlay.yaml",
+            "--set",
+            "http_server.port=9090",
+            "--set",
+            "telemetry.level=info,kube=debug",
+            "--print-config",
+            "check-config",
+        ])
+        .unwrap();
+
+        assert_eq!(Some(PathBuf::from("/etc/rust-kata-004")), cli.config_dir);
+        assert_eq!(Some("production,eu-west".to_string()), cli.environment);
+        assert_eq!(
+            vec![PathBuf::from("/etc/rust-kata-004/overlay.yaml")],
+            cli.overlays
+        );
+        assert_eq!(
+            vec![
+                ("http_server.port".to_string(), "9090".to_string()),
+                ("telemetry.level".to_string(), "info,kube=debug".to_string()),
+            ],
+            cli.overrides
+        );
+        assert!(cli.print_config);
+        assert_eq!(Command::CheckConfig, cli.command);
+    }
+
+    #[test]
+    fn source_uses_options() {
+        let cli = parse(&["--config-dir", "config", "--set", "a.b=c"]).unwrap();
+
+        let source = cli.source();
+
+        assert_eq!(PathBuf::from("config"), source.directory);
+        assert_eq!(None, source.environment);
+        assert_eq!(vec![("a.b".to_string(), "c".to_string())], source.overrides);
+    }
+
+    #[test]
+    fn parse_rejects_invalid_arguments() {
+        assert!(parse(&["--config-dir"]).is_err());
+        assert!(parse(&["--set", "port"]).is_err());
+        assert!(parse(&["--set", "=9090"]).is_err());
+        assert!(parse(&["serve"]).is_err());
+    }
+}
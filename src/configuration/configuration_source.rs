@@ -0,0 +1,172 @@
+use super::environment::Environment;
+use super::Configuration;
+use config::{Config, ConfigError, File};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+/// Files and overrides a `Configuration` is loaded from, kept to load it again when reloading.
+#[derive(Clone, Debug)]
+pub struct ConfigurationSource {
+    pub directory: PathBuf,
+    /// Comma separated environments to merge, `APP_ENVIRONMENT` or `local` when unset.
+    pub environment: Option<String>,
+    /// Files merged after the environments when they exist.
+    pub overlays: Vec<PathBuf>,
+    pub overrides: Vec<(String, String)>,
+}
+
+impl Default for ConfigurationSource {
+    fn default() -> Self {
+        Self {
+            directory: std::env::current_dir()
+                .expect("Failed to determine current directory.")
+                .join("configuration"),
+            environment: None,
+            overlays: Vec::new(),
+            overrides: Vec::new(),
+        }
+    }
+}
+
+impl ConfigurationSource {
+    /// Applies the overrides after the files and environment variables.
+    pub fn overrides(mut self, overrides: &[(&str, &str)]) -> Self {
+        self.overrides.extend(
+            overrides
+                .iter()
+                .map(|&(key, value)| (key.to_string(), value.to_string())),
+        );
+        self
+    }
+
+    /// Environments to merge, in order.
+    pub fn environments(&self) -> Result<Vec<Environment>, ConfigError> {
+        let value = self
+            .environment
+            .clone()
+            .or_else(|| std::env::var("APP_ENVIRONMENT").ok())
+            .unwrap_or_else(|| Environment::default().as_str().to_owned());
+
+        Environment::parse_profiles(&value).map_err(ConfigError::Message)
+    }
+
+    /// Merges `default`, each environment file, the overlays, `APP__` variables and the
+    /// overrides.
+    pub fn load(&self) -> Result<Configuration, ConfigError> {
+        let mut config = Config::default();
+        config.merge(File::from(self.directory.join("default")).required(true))?;
+
+        for environment in self.environments()? {
+            if !has_file(&self.directory, environment.as_str()) {
+                return Err(ConfigError::Message(format!(
+                    "{} is not a supported environment, {} has no `{}` file. Use one of: {}.",
+                    environment.as_str(),
+                    self.directory.display(),
+                    environment.as_str(),
+                    available(&self.directory).join(", ")
+                )));
+            }
+            config.merge(File::from(self.directory.join(environment.as_str())).required(true))?;
+        }
+
+        for overlay in &self.overlays {
+            config.merge(File::from(overlay.as_path()).required(false))?;
+        }
+
+        config.merge(config::Environment::with_prefix("APP").separator("__"))?;
+
+        for (key, value) in &self.overrides {
+            config.set(key, value.as_str())?;
+        }
+
+        config.try_into()
+    }
+}
+
+/// Returns true if the directory has a file named `stem`, with any extension.
+fn has_file(directory: &Path, stem: &str) -> bool {
+    files(directory).any(|path| path.file_stem() == Some(OsStr::new(stem)))
+}
+
+/// Environments with a file in the directory, in order.
+fn available(directory: &Path) -> Vec<String> {
+    let mut environments: Vec<String> = files(directory)
+        .filter_map(|path| Some(path.file_stem()?.to_str()?.to_owned()))
+        .filter(|name| name != "default" && !name.starts_with('.'))
+        .collect();
+    environments.sort_unstable();
+    environments.dedup();
+    environments
+}
+
+fn files(directory: &Path) -> impl Iterator<Item = PathBuf> {
+    std::fs::read_dir(directory)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::available;
+    use crate::configuration::test_directory::TestDirectory;
+    use std::path::PathBuf;
+
+    #[test]
+    fn load_merges_profiles_then_overlays_in_order() {
+        let directory = TestDirectory::new(
+            "source-profiles",
+            &["default.yaml"],
+            &[
+                (
+                    "production.yaml",
+                    "http_server:\n  host: 0.0.0.0\n  port: 80\n",
+                ),
+                ("eu-west.yaml", "http_server:\n  port: 8081\n"),
+                ("overlay.yaml", "controller:\n  requeue_seconds: 60\n"),
+            ],
+        );
+        let mut source = directory.source();
+        source.environment = Some("production,eu-west".to_string());
+        source.overlays = vec![
+            directory.path.join("overlay.yaml"),
+            directory.path.join("missing.yaml"),
+        ];
+
+        let configuration = source.load().unwrap();
+
+        assert_eq!("0.0.0.0", configuration.http_server.host);
+        assert_eq!(8081, configuration.http_server.port);
+        assert_eq!(60, configuration.controller.requeue_seconds);
+    }
+
+    #[test]
+    fn load_reports_missing_environment_file() {
+        let directory = TestDirectory::new(
+            "source-missing",
+            &["default.yaml"],
+            &[("local.yaml", "http_server:\n  host: localhost\n")],
+        );
+        let mut source = directory.source();
+        source.environment = Some("local,staging".to_string());
+
+        let error = source.load().err().unwrap().to_string();
+
+        assert_eq!(
+            format!(
+                "staging is not a supported environment, {} has no `staging` file. Use one of: local.",
+                directory.path.display()
+            ),
+            error
+        );
+    }
+
+    #[test]
+    fn available_lists_environment_files() {
+        let environments = available(&PathBuf::from("configuration"));
+
+        assert_eq!(vec!["local", "production"], environments);
+    }
+}
@@ -0,0 +1,115 @@
+use std::time::Duration;
+
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct ControllerConfiguration {
+    /// Namespaces to watch, every namespace when empty.
+    #[serde(default)]
+    pub namespaces: Vec<String>,
+    #[serde(default)]
+    pub label_selector: Option<String>,
+    #[serde(default)]
+    pub field_selector: Option<String>,
+    pub requeue_seconds: u64,
+    pub error_requeue_seconds: u64,
+    /// Field manager owning the status applied by the controller.
+    pub field_manager: String,
+}
+
+impl ControllerConfiguration {
+    /// Delay before reconciling a `TorHiddenService` again after success.
+    pub fn requeue(&self) -> Duration {
+        Duration::from_secs(self.requeue_seconds)
+    }
+
+    /// Delay before reconciling a `TorHiddenService` again after failure.
+    pub fn error_requeue(&self) -> Duration {
+        Duration::from_secs(self.error_requeue_seconds)
+    }
+
+    pub(crate) fn validate(&self, errors: &mut Vec<String>) {
+        for namespace in &self.namespaces {
+            if !is_dns_label(namespace) {
+                errors.push(format!(
+                    "controller.namespaces: `{}` is not a valid namespace name",
+                    namespace
+                ));
+            }
+        }
+        for (key, selector) in &[
+            ("label_selector", &self.label_selector),
+            ("field_selector", &self.field_selector),
+        ] {
+            if matches!(selector, Some(selector) if selector.trim().is_empty()) {
+                errors.push(format!("controller.{} must not be empty when set", key));
+            }
+        }
+        if self.requeue_seconds == 0 {
+            errors.push("controller.requeue_seconds must be greater than 0".to_string());
+        }
+        if self.error_requeue_seconds == 0 {
+            errors.push("controller.error_requeue_seconds must be greater than 0".to_string());
+        }
+        if self.field_manager.is_empty() || self.field_manager.len() > 128 {
+            errors.push("controller.field_manager must be 1 to 128 characters long".to_string());
+        }
+    }
+}
+
+/// Returns true if the name is an RFC 1123 label, as required of namespace names.
+fn is_dns_label(name: &str) -> bool {
+    let alphanumeric = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit();
+
+    !name.is_empty()
+        && name.len() <= 63
+        && name.chars().all(|c| alphanumeric(c) || c == '-')
+        && name.starts_with(alphanumeric)
+        && name.ends_with(alphanumeric)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_configuration() -> ControllerConfiguration {
+        ControllerConfiguration {
+            namespaces: vec!["default".to_string()],
+            label_selector: None,
+            field_selector: None,
+            requeue_seconds: 1800,
+            error_requeue_seconds: 360,
+            field_manager: "rust-kata-004".to_string(),
+        }
+    }
+
+    #[test]
+    fn valid_configuration_has_no_errors() {
+        let mut errors = Vec::new();
+
+        create_configuration().validate(&mut errors);
+
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn validate_reports_every_error() {
+        let mut configuration = create_configuration();
+        configuration.namespaces = vec!["Default".to_string(), "-tor".to_string()];
+        configuration.label_selector = Some(" ".to_string());
+        configuration.requeue_seconds = 0;
+        configuration.field_manager = String::new();
+        let mut errors = Vec::new();
+
+        configuration.validate(&mut errors);
+
+        assert_eq!(
+            vec![
+                "controller.namespaces: `Default` is not a valid namespace name",
+                "controller.namespaces: `-tor` is not a valid namespace name",
+                "controller.label_selector must not be empty when set",
+                "controller.requeue_seconds must be greater than 0",
+                "controller.field_manager must be 1 to 128 characters long",
+            ],
+            errors
+        );
+    }
+}
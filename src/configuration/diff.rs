@@ -0,0 +1,128 @@
+use super::redact::is_secret;
+use super::Configuration;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Setting whose value differs between two configurations.
+#[derive(Debug, PartialEq)]
+pub struct Change {
+    pub key: String,
+    pub old: String,
+    pub new: String,
+}
+
+/// Returns every setting changed from `old` to `new`, ordered by key, with secret values
+/// redacted.
+pub fn diff(old: &Configuration, new: &Configuration) -> Vec<Change> {
+    let old = flatten(old);
+    let mut new = flatten(new);
+
+    old.into_iter()
+        .filter_map(|(key, old)| {
+            let new = new.remove(&key).unwrap_or_else(|| "null".to_string());
+            if old == new {
+                None
+            } else if is_secret(&key) {
+                Some(Change {
+                    old: redact_setting(old),
+                    new: redact_setting(new),
+                    key,
+                })
+            } else {
+                Some(Change { key, old, new })
+            }
+        })
+        .collect()
+}
+
+fn redact_setting(value: String) -> String {
+    if value == "null" {
+        value
+    } else {
+        "\"<redacted>\"".to_string()
+    }
+}
+
+/// Flattens the configuration into dotted keys and their values.
+pub(crate) fn flatten(configuration: &Configuration) -> BTreeMap<String, String> {
+    let value = serde_json::to_value(configuration).expect("Failed to serialize configuration.");
+    let mut settings = BTreeMap::new();
+    insert(&mut settings, String::new(), value);
+    settings
+}
+
+fn insert(settings: &mut BTreeMap<String, String>, key: String, value: Value) {
+    match value {
+        Value::Object(map) => {
+            for (name, value) in map {
+                let key = if key.is_empty() {
+                    name
+                } else {
+                    format!("{}.{}", key, name)
+                };
+                insert(settings, key, value);
+            }
+        }
+        value => {
+            settings.insert(key, value.to_string());
+        }
+    }
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} -> {}", self.key, self.old, self.new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, Change};
+    use crate::configuration::Configuration;
+
+    #[test]
+    fn diff_lists_changed_settings() {
+        let old = Configuration::load(&[]).unwrap();
+        let new = Configuration::load(&[
+            ("controller.requeue_seconds", "60"),
+            ("tor_pool.program", "/usr/bin/tor"),
+        ])
+        .unwrap();
+
+        let changes = diff(&old, &new);
+
+        assert_eq!(
+            vec![
+                Change {
+                    key: "controller.requeue_seconds".to_string(),
+                    old: "1800".to_string(),
+                    new: "60".to_string(),
+                },
+                Change {
+                    key: "tor_pool.program".to_string(),
+                    old: "\"tor\"".to_string(),
+                    new: "\"/usr/bin/tor\"".to_string(),
+                },
+            ],
+            changes
+        );
+        assert_eq!(
+            "controller.requeue_seconds: 1800 -> 60",
+            changes[0].to_string()
+        );
+        assert!(diff(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn diff_redacts_secret_settings() {
+        let old = Configuration::load(&[]).unwrap();
+        let new = Configuration::load(&[("http_server.admin_token", "hunter2")]).unwrap();
+
+        let changes = diff(&old, &new);
+
+        assert_eq!(
+            "http_server.admin_token: null -> \"<redacted>\"",
+            changes[0].to_string()
+        );
+    }
+}
@@ -1,17 +1,26 @@
 use std::convert::TryFrom;
 
-#[derive(Debug, PartialEq)]
-pub enum Environment {
-    Local,
-    Production,
-}
+/// Named profile merged from `configuration/<name>.yaml`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Environment(String);
 
 impl Environment {
     pub fn as_str(&self) -> &str {
-        match self {
-            Environment::Local => "local",
-            Environment::Production => "production",
-        }
+        &self.0
+    }
+
+    /// Parses comma separated profiles, merged in order so later ones take precedence.
+    pub fn parse_profiles(value: &str) -> Result<Vec<Self>, String> {
+        value
+            .split(',')
+            .map(|name| Self::try_from(name.trim().to_owned()))
+            .collect()
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self("local".to_owned())
     }
 }
 
@@ -19,13 +28,18 @@ impl TryFrom<String> for Environment {
     type Error = String;
 
     fn try_from(value: String) -> Result<Self, Self::Error> {
-        match value.to_lowercase().as_str() {
-            "local" => Ok(Self::Local),
-            "production" => Ok(Self::Production),
-            other => Err(format!(
-                "{} is not a supported environment. Use either `local` or `production`.",
-                other
-            )),
+        let value = value.to_lowercase();
+        let valid = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+
+        if value.is_empty() {
+            Err("Environment names must not be empty.".to_owned())
+        } else if !value.chars().all(valid) {
+            Err(format!(
+                "{} is not a valid environment name. Use letters, digits, `-` and `_`.",
+                value
+            ))
+        } else {
+            Ok(Self(value))
         }
     }
 }
@@ -37,26 +51,47 @@ mod tests {
 
     #[test]
     fn as_str() {
-        assert_eq!("local", Environment::Local.as_str());
-        assert_eq!("production", Environment::Production.as_str());
+        assert_eq!("local", Environment::default().as_str());
+        assert_eq!(
+            "staging",
+            Environment::try_from("Staging".to_owned())
+                .unwrap()
+                .as_str()
+        );
     }
 
     #[test]
     fn try_from() {
         assert_eq!(
-            Ok(Environment::Local),
+            Ok(Environment::default()),
             Environment::try_from("local".to_owned())
         );
         assert_eq!(
-            Ok(Environment::Production),
-            Environment::try_from("production".to_owned())
+            Ok(Environment("eu-west_1".to_owned())),
+            Environment::try_from("eu-west_1".to_owned())
         );
         assert_eq!(
             Err(
-                "other is not a supported environment. Use either `local` or `production`."
+                "../secrets is not a valid environment name. Use letters, digits, `-` and `_`."
                     .to_owned()
             ),
-            Environment::try_from("other".to_owned())
+            Environment::try_from("../secrets".to_owned())
+        );
+        assert_eq!(
+            Err("Environment names must not be empty.".to_owned()),
+            Environment::try_from(String::new())
+        );
+    }
+
+    #[test]
+    fn parse_profiles() {
+        assert_eq!(
+            Ok(vec![
+                Environment("production".to_owned()),
+                Environment("eu-west".to_owned())
+            ]),
+            Environment::parse_profiles("production, eu-west")
         );
+        assert!(Environment::parse_profiles("production,,eu-west").is_err());
     }
 }
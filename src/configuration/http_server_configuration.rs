@@ -1,13 +1,25 @@
 use std::net::TcpListener;
 
-#[derive(serde::Deserialize)]
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
 pub struct HttpServerConfiguration {
     pub host: String,
     pub port: u16,
+    /// Bearer token required by the `/admin` routes, which are not served when unset.
+    #[serde(default)]
+    pub admin_token: Option<String>,
 }
 
 impl HttpServerConfiguration {
     pub fn tcp_listener(&self) -> std::io::Result<TcpListener> {
         TcpListener::bind(format!("{}:{}", self.host, self.port))
     }
+
+    pub(crate) fn validate(&self, errors: &mut Vec<String>) {
+        if self.host.is_empty() {
+            errors.push("http_server.host must not be empty".to_string());
+        }
+        if self.admin_token.as_deref() == Some("") {
+            errors.push("http_server.admin_token must not be empty".to_string());
+        }
+    }
 }
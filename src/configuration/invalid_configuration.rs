@@ -0,0 +1,17 @@
+/// Every problem found while validating a `Configuration`.
+#[derive(Debug, PartialEq)]
+pub struct InvalidConfiguration {
+    pub errors: Vec<String>,
+}
+
+impl std::error::Error for InvalidConfiguration {}
+
+impl std::fmt::Display for InvalidConfiguration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid configuration:")?;
+        for error in &self.errors {
+            write!(f, "\n  - {}", error)?;
+        }
+        Ok(())
+    }
+}
@@ -1,37 +1,93 @@
+mod configuration_source;
+mod controller_configuration;
+mod diff;
 mod environment;
 mod http_server_configuration;
+mod invalid_configuration;
+mod redact;
+mod reload_configuration;
+mod shutdown_configuration;
+mod telemetry_configuration;
+#[cfg(test)]
+pub(crate) mod test_directory;
+mod tor_pool_configuration;
 
-use config::{Config, ConfigError, File};
-use environment::Environment;
-use http_server_configuration::HttpServerConfiguration;
-use std::convert::TryInto;
+use config::ConfigError;
 
-#[derive(serde::Deserialize)]
+pub use configuration_source::ConfigurationSource;
+pub use controller_configuration::ControllerConfiguration;
+pub use diff::{diff, Change};
+pub use environment::Environment;
+pub use http_server_configuration::HttpServerConfiguration;
+pub use invalid_configuration::InvalidConfiguration;
+pub use redact::redact;
+pub use reload_configuration::ReloadConfiguration;
+pub use shutdown_configuration::ShutdownConfiguration;
+pub use telemetry_configuration::{LogFormat, TelemetryConfiguration};
+pub use tor_pool_configuration::TorPoolConfiguration;
+
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
 pub struct Configuration {
+    pub controller: ControllerConfiguration,
     pub http_server: HttpServerConfiguration,
+    pub reload: ReloadConfiguration,
+    pub shutdown: ShutdownConfiguration,
+    pub telemetry: TelemetryConfiguration,
+    pub tor_pool: TorPoolConfiguration,
 }
 
 impl Configuration {
+    /// Loads the `configuration` directory of the current directory, then the overrides.
     pub fn load(overrides: &[(&str, &str)]) -> Result<Configuration, ConfigError> {
-        let configuration_directory = std::env::current_dir()
-            .expect("Failed to determine current directory.")
-            .join("configuration");
-
-        let environment: Environment = std::env::var("APP_ENVIRONMENT")
-            .unwrap_or_else(|_| "local".into())
-            .try_into()
-            .expect("Failed to parse APP_ENVIRONMENT.");
-
-        let mut config = Config::default();
-        config
-            .merge(File::from(configuration_directory.join("default")).required(true))?
-            .merge(File::from(configuration_directory.join(environment.as_str())).required(true))?
-            .merge(config::Environment::with_prefix("APP").separator("__"))?;
-
-        for &(key, value) in overrides {
-            config.set(key, value)?;
+        ConfigurationSource::default().overrides(overrides).load()
+    }
+
+    /// Checks every section, reporting all errors at once.
+    pub fn validate(&self) -> Result<(), InvalidConfiguration> {
+        let mut errors = Vec::new();
+        self.controller.validate(&mut errors);
+        self.http_server.validate(&mut errors);
+        self.reload.validate(&mut errors);
+        self.shutdown.validate(&mut errors);
+        self.telemetry.validate(&mut errors);
+        self.tor_pool.validate(&mut errors);
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(InvalidConfiguration { errors })
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Configuration;
+
+    #[test]
+    fn default_configuration_is_valid() {
+        let configuration = Configuration::load(&[]).unwrap();
+
+        assert_eq!(Ok(()), configuration.validate());
+    }
+
+    #[test]
+    fn validate_reports_errors_of_every_section() {
+        let configuration = Configuration::load(&[
+            ("controller.requeue_seconds", "0"),
+            ("shutdown.timeout_seconds", "0"),
+            ("telemetry.level", "tor=loud"),
+        ])
+        .unwrap();
+
+        let errors = configuration.validate().unwrap_err().errors;
 
-        config.try_into()
+        assert_eq!(3, errors.len());
+        assert_eq!(
+            "controller.requeue_seconds must be greater than 0",
+            errors[0]
+        );
+        assert_eq!("shutdown.timeout_seconds must be greater than 0", errors[1]);
+        assert!(errors[2].starts_with("telemetry.level: `tor=loud` is not a valid filter"));
     }
 }
@@ -0,0 +1,62 @@
+use super::Configuration;
+use serde_json::Value;
+
+/// Parts of setting names marking their values as secrets.
+const SECRETS: &[&str] = &["password", "secret", "token", "cookie", "key"];
+
+/// Serializes the configuration, replacing the value of every secret setting.
+pub fn redact(configuration: &Configuration) -> Value {
+    let mut value =
+        serde_json::to_value(configuration).expect("Failed to serialize configuration.");
+    redact_value(&mut value);
+    value
+}
+
+/// Whether the setting `name`, or the last part of a dotted key, holds a secret.
+pub(crate) fn is_secret(name: &str) -> bool {
+    let name = name.rsplit('.').next().unwrap_or(name).to_lowercase();
+    SECRETS.iter().any(|secret| name.contains(secret))
+}
+
+fn redact_value(value: &mut Value) {
+    if let Value::Object(map) = value {
+        for (name, value) in map.iter_mut() {
+            if is_secret(name) && !value.is_null() {
+                *value = Value::String("<redacted>".to_string());
+            } else {
+                redact_value(value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::redact_value;
+
+    #[test]
+    fn redact_value_replaces_secrets_only() {
+        let mut value = serde_json::json!({
+            "tor_pool": {
+                "program": "tor",
+                "hashed_control_password": "16:ABCDEF",
+                "cookie_file": null,
+            },
+            "api_token": "token",
+        });
+
+        redact_value(&mut value);
+
+        assert_eq!(
+            serde_json::json!({
+                "tor_pool": {
+                    "program": "tor",
+                    "hashed_control_password": "<redacted>",
+                    "cookie_file": null,
+                },
+                "api_token": "<redacted>",
+            }),
+            value
+        );
+    }
+}
@@ -0,0 +1,27 @@
+use std::time::Duration;
+
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct ReloadConfiguration {
+    /// Watches the configuration directory, applying changes which do not need a restart.
+    pub enabled: bool,
+    pub interval_milliseconds: u64,
+    /// Time the files must stay unchanged before being reloaded.
+    pub debounce_milliseconds: u64,
+}
+
+impl ReloadConfiguration {
+    /// Time between checks of the configuration directory.
+    pub fn interval(&self) -> Duration {
+        Duration::from_millis(self.interval_milliseconds)
+    }
+
+    pub fn debounce(&self) -> Duration {
+        Duration::from_millis(self.debounce_milliseconds)
+    }
+
+    pub(crate) fn validate(&self, errors: &mut Vec<String>) {
+        if self.interval_milliseconds == 0 {
+            errors.push("reload.interval_milliseconds must be greater than 0".to_string());
+        }
+    }
+}
@@ -0,0 +1,19 @@
+use std::time::Duration;
+
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct ShutdownConfiguration {
+    pub timeout_seconds: u64,
+}
+
+impl ShutdownConfiguration {
+    /// Time allowed for in-flight reconciles and HTTP requests to finish.
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_seconds)
+    }
+
+    pub(crate) fn validate(&self, errors: &mut Vec<String>) {
+        if self.timeout_seconds == 0 {
+            errors.push("shutdown.timeout_seconds must be greater than 0".to_string());
+        }
+    }
+}
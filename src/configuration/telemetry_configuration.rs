@@ -0,0 +1,57 @@
+use std::convert::TryFrom;
+use tracing_subscriber::EnvFilter;
+
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct TelemetryConfiguration {
+    /// Default filter directives, overridden by `RUST_LOG`.
+    pub level: String,
+    pub format: LogFormat,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum LogFormat {
+    Json,
+    Pretty,
+}
+
+impl TelemetryConfiguration {
+    pub(crate) fn validate(&self, errors: &mut Vec<String>) {
+        if let Err(error) = EnvFilter::try_new(&self.level) {
+            errors.push(format!(
+                "telemetry.level: `{}` is not a valid filter: {}",
+                self.level, error
+            ));
+        }
+    }
+}
+
+impl LogFormat {
+    pub fn as_str(&self) -> &str {
+        match self {
+            LogFormat::Json => "json",
+            LogFormat::Pretty => "pretty",
+        }
+    }
+}
+
+impl From<LogFormat> for String {
+    fn from(format: LogFormat) -> Self {
+        format.as_str().to_string()
+    }
+}
+
+impl TryFrom<String> for LogFormat {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "pretty" => Ok(Self::Pretty),
+            other => Err(format!(
+                "{} is not a supported log format. Use either `json` or `pretty`.",
+                other
+            )),
+        }
+    }
+}
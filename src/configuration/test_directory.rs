@@ -0,0 +1,46 @@
+use super::ConfigurationSource;
+use std::path::{Path, PathBuf};
+
+/// Configuration directory created in the temporary directory for a test, deleted when dropped
+/// so a failing assertion does not leave it behind.
+pub struct TestDirectory {
+    pub path: PathBuf,
+}
+
+impl TestDirectory {
+    /// Copies the `copies` from the `configuration` directory then writes the `files`.
+    pub fn new(name: &str, copies: &[&str], files: &[(&str, &str)]) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("rust-kata-004-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&path).unwrap();
+        let directory = Self { path };
+
+        for file in copies {
+            std::fs::copy(
+                Path::new("configuration").join(file),
+                directory.path.join(file),
+            )
+            .unwrap();
+        }
+        for (file, contents) in files {
+            std::fs::write(directory.path.join(file), contents).unwrap();
+        }
+        directory
+    }
+
+    /// Source loading from the directory.
+    pub fn source(&self) -> ConfigurationSource {
+        ConfigurationSource {
+            directory: self.path.clone(),
+            environment: None,
+            overlays: Vec::new(),
+            overrides: Vec::new(),
+        }
+    }
+}
+
+impl Drop for TestDirectory {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
@@ -0,0 +1,63 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct TorPoolConfiguration {
+    pub size: usize,
+    /// Name or path of the Tor binary.
+    pub program: String,
+    /// Directory holding the data directory and torrc of every instance.
+    pub directory: PathBuf,
+    /// Control port of the first instance, incremented for each following instance.
+    #[serde(default)]
+    pub control_port: Option<u16>,
+    /// Torrc included by the torrc of every instance.
+    #[serde(default)]
+    pub torrc: Option<PathBuf>,
+    /// Window over which changes to the hidden services of an instance are coalesced into a
+    /// single reload of Tor.
+    pub reload_debounce_milliseconds: u64,
+}
+
+impl TorPoolConfiguration {
+    pub fn reload_debounce(&self) -> Duration {
+        Duration::from_millis(self.reload_debounce_milliseconds)
+    }
+
+    /// Whether the control ports counting up from `control_port` are valid for each of `size`
+    /// instances.
+    pub(crate) fn control_ports_fit(&self, size: usize) -> bool {
+        match self.control_port {
+            Some(control_port) => {
+                let last = usize::from(control_port) + size.saturating_sub(1);
+                control_port != 0 && last <= usize::from(u16::MAX)
+            }
+            None => true,
+        }
+    }
+
+    pub(crate) fn validate(&self, errors: &mut Vec<String>) {
+        if self.program.is_empty() {
+            errors.push("tor_pool.program must not be empty".to_string());
+        }
+        if self.directory.as_os_str().is_empty() {
+            errors.push("tor_pool.directory must not be empty".to_string());
+        }
+        if let Some(control_port) = self.control_port {
+            if !self.control_ports_fit(self.size) {
+                errors.push(format!(
+                    "tor_pool.control_port: {} leaves no valid port for each of {} instances",
+                    control_port, self.size
+                ));
+            }
+        }
+        if let Some(torrc) = &self.torrc {
+            if !torrc.is_file() {
+                errors.push(format!(
+                    "tor_pool.torrc: `{}` does not exist",
+                    torrc.display()
+                ));
+            }
+        }
+    }
+}
@@ -1,3 +1,13 @@
+use crate::configuration::ControllerConfiguration;
+use crate::shutdown::Shutdown;
+use crate::tor::Pool;
+use std::sync::Arc;
+use tokio::sync::{watch, Mutex};
+
+#[derive(Clone)]
 pub struct Data {
     pub client: kube::Client,
+    pub configuration: watch::Receiver<ControllerConfiguration>,
+    pub pool: Arc<Mutex<Pool>>,
+    pub shutdown: Shutdown,
 }
@@ -1,10 +1,25 @@
 #[derive(Debug)]
-pub struct Error {}
+pub enum Error {
+    /// Tor could not be reloaded with the change to the hidden service.
+    Tor(tor_sub_process::Error),
+    /// The Kubernetes API rejected a request.
+    Kube(kube::Error),
+}
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Tor(error) => Some(error),
+            Error::Kube(error) => Some(error),
+        }
+    }
+}
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            Error::Tor(error) => write!(f, "Failed to reload Tor: {}", error),
+            Error::Kube(error) => write!(f, "Kubernetes API request failed: {}", error),
+        }
     }
 }
@@ -6,26 +6,65 @@ use kube::api::ListParams;
 use kube::{Api, Client};
 use kube_runtime::controller::Context;
 use kube_runtime::Controller;
+use std::sync::Arc;
+use tokio::sync::{watch, Mutex};
 
 use super::data::Data;
 use super::tor_hidden_service_spec::TorHiddenService;
 use super::{error_policy, reconcile};
+use crate::configuration::ControllerConfiguration;
+use crate::shutdown::Shutdown;
+use crate::tor::Pool;
 
 #[derive(Clone)]
-pub struct Manager {}
+pub struct Manager {
+    pool: Arc<Mutex<Pool>>,
+}
 
 impl Manager {
     pub(crate) async fn new(
         client: Client,
+        configuration: watch::Receiver<ControllerConfiguration>,
+        pool: Arc<Mutex<Pool>>,
+        shutdown: Shutdown,
     ) -> (Self, Pin<Box<dyn Future<Output = ()> + Send + 'static>>) {
         let context = Context::new(Data {
             client: client.clone(),
+            configuration: configuration.clone(),
+            pool: pool.clone(),
+            shutdown,
         });
 
-        let api = Api::<TorHiddenService>::all(client);
+        let configuration = configuration.borrow().clone();
+        let mut list_params = ListParams::default();
+        if let Some(labels) = &configuration.label_selector {
+            list_params = list_params.labels(labels);
+        }
+        if let Some(fields) = &configuration.field_selector {
+            list_params = list_params.fields(fields);
+        }
+
+        // one controller per watched namespace, or a single one for every namespace
+        let apis = if configuration.namespaces.is_empty() {
+            vec![Api::<TorHiddenService>::all(client)]
+        } else {
+            configuration
+                .namespaces
+                .iter()
+                .map(|namespace| Api::<TorHiddenService>::namespaced(client.clone(), namespace))
+                .collect()
+        };
+        let controllers = apis.into_iter().map(|api| {
+            Controller::new(api, list_params.clone())
+                .run(
+                    reconcile::reconcile,
+                    error_policy::error_policy,
+                    context.clone(),
+                )
+                .boxed()
+        });
 
-        let drainer = Controller::new(api, ListParams::default())
-            .run(reconcile::reconcile, error_policy::error_policy, context)
+        let drainer = futures::stream::select_all(controllers)
             .filter_map(|x| async move { std::result::Result::ok(x) })
             .for_each(|o| {
                 tracing::info!("Reconciled {:?}", o);
@@ -33,6 +72,11 @@ impl Manager {
             })
             .boxed();
 
-        (Self {}, drainer)
+        (Self { pool }, drainer)
+    }
+
+    /// Returns a snapshot of every supervised Tor instance.
+    pub async fn tor_statuses(&self) -> Vec<tor_sub_process::Status> {
+        self.pool.lock().await.statuses()
     }
 }
@@ -5,7 +5,12 @@ use kube_runtime::controller::{Context, ReconcilerAction};
 use super::data::Data;
 use super::error::Error;
 use super::tor_hidden_service_spec::TorHiddenService;
-use super::tor_hidden_service_status::TorHiddenServiceStatus;
+use super::tor_hidden_service_status::{TorHiddenServiceCondition, TorHiddenServiceStatus};
+use crate::tor::HiddenService;
+use tor_sub_process::PendingReload;
+
+/// Keeps a deleted TorHiddenService until its hidden service is removed from Tor.
+const FINALIZER: &str = "agabani.rust-kata-004/hidden-service";
 
 #[tracing::instrument(skip(ctx))]
 pub async fn reconcile(
@@ -14,32 +19,143 @@ pub async fn reconcile(
 ) -> Result<ReconcilerAction, Error> {
     let name = Meta::name(&tor_hidden_service);
 
+    // refuse new work once shutting down
+    let _in_flight = match ctx.get_ref().shutdown.enter() {
+        Some(in_flight) => in_flight,
+        None => {
+            tracing::info!(
+                "Shutting down, skipping reconcile of TorHiddenService {}",
+                name
+            );
+            return Ok(ReconcilerAction {
+                requeue_after: None,
+            });
+        }
+    };
+
     tracing::info!(
         "Reconcile TorHiddenService {}: {:?}",
         name,
         tor_hidden_service
     );
 
+    let configuration = ctx.get_ref().configuration.borrow().clone();
+
     // create client
     let client = ctx.get_ref().client.clone();
     let namespace = Meta::namespace(&tor_hidden_service).expect("Failed to get service namespace.");
     let api: Api<TorHiddenService> = Api::namespaced(client, &namespace);
+    let key = format!("{}/{}", namespace, name);
+    let finalizers = Meta::meta(&tor_hidden_service)
+        .finalizers
+        .clone()
+        .unwrap_or_default();
+
+    // withdraw the hidden service from Tor before Kubernetes deletes it
+    if Meta::meta(&tor_hidden_service).deletion_timestamp.is_some() {
+        if finalizers.iter().any(|finalizer| finalizer == FINALIZER) {
+            let pending = ctx.get_ref().pool.lock().await.remove(&key).await;
+            reloaded(ctx.get_ref(), &key, pending).await?;
+            let remaining = finalizers
+                .into_iter()
+                .filter(|finalizer| finalizer != FINALIZER)
+                .collect();
+            set_finalizers(&api, &name, remaining).await?;
+        }
+        return Ok(ReconcilerAction {
+            requeue_after: None,
+        });
+    }
+    if !finalizers.iter().any(|finalizer| finalizer == FINALIZER) {
+        let mut finalizers = finalizers;
+        finalizers.push(FINALIZER.to_string());
+        set_finalizers(&api, &name, finalizers).await?;
+    }
+
+    // assign hidden service to a tor instance, waiting for the reload without holding the pool
+    let hidden_service = HiddenService {
+        host: tor_hidden_service.spec.host.clone(),
+        port: tor_hidden_service.spec.port,
+    };
+    let pending = ctx
+        .get_ref()
+        .pool
+        .lock()
+        .await
+        .insert(&key, hidden_service)
+        .await;
+    reloaded(ctx.get_ref(), &key, pending).await?;
+    let pool = ctx.get_ref().pool.lock().await;
+    let tor = pool.status(&key);
+    let hostname = pool.hostname(&key);
+    std::mem::drop(pool);
+
+    // Tor writes the hostname file once it has published the hidden service
+    if hostname.is_none() {
+        tracing::info!(
+            "Hidden service {} is not published yet, reconciling again later",
+            key
+        );
+        return Ok(ReconcilerAction {
+            requeue_after: Some(configuration.error_requeue()),
+        });
+    }
 
     // calculate new status
+    let conditions = tor
+        .map(|tor| vec![TorHiddenServiceCondition::degraded(&tor)])
+        .unwrap_or_default();
     let patch = Patch::Apply(serde_json::json!({
         "apiVersion": "agabani.rust-kata-004/v1",
         "kind": "TorHiddenService",
         "status": TorHiddenServiceStatus {
-            hostname: Some("example".to_string())
+            hostname,
+            conditions,
         }
     }));
-    let patch_params = PatchParams::apply("cntrlr").force();
+    let patch_params = PatchParams::apply(&configuration.field_manager).force();
     let _o = api
         .patch_status(&name, &patch_params, &patch)
         .await
-        .expect("TODO: error handling");
+        .map_err(Error::Kube)?;
 
     Ok(ReconcilerAction {
-        requeue_after: Some(std::time::Duration::from_secs(1800)),
+        requeue_after: Some(configuration.requeue()),
     })
 }
+
+/// Waits for the reload queued by the pool. A failed reload marks the instance to be reloaded
+/// again when the hidden service is retried.
+async fn reloaded(
+    data: &Data,
+    key: &str,
+    pending: Result<Option<PendingReload>, tor_sub_process::Error>,
+) -> Result<(), Error> {
+    let reloaded = match pending {
+        Ok(Some(pending)) => pending.wait().await.map(|_| ()),
+        Ok(None) => Ok(()),
+        Err(error) => Err(error),
+    };
+
+    if let Err(error) = reloaded {
+        tracing::error!(%error, "failed to publish hidden service {}", key);
+        data.pool.lock().await.invalidate(key);
+        return Err(Error::Tor(error));
+    }
+    Ok(())
+}
+
+/// Replaces the finalizers of a TorHiddenService.
+async fn set_finalizers(
+    api: &Api<TorHiddenService>,
+    name: &str,
+    finalizers: Vec<String>,
+) -> Result<(), Error> {
+    let patch = Patch::Merge(serde_json::json!({
+        "metadata": { "finalizers": finalizers }
+    }));
+    api.patch(name, &PatchParams::default(), &patch)
+        .await
+        .map_err(Error::Kube)?;
+    Ok(())
+}
@@ -13,7 +13,7 @@ use super::tor_hidden_service_status::TorHiddenServiceStatus;
     status = "TorHiddenServiceStatus"
 )]
 pub struct TorHiddenServiceSpec {
-    name: String,
-    host: String,
-    port: u16,
+    pub name: String,
+    pub host: String,
+    pub port: u16,
 }
@@ -1,4 +1,94 @@
+use tor_sub_process::{State, Status};
+
 #[derive(Clone, Debug, schemars::JsonSchema, serde::Serialize, serde::Deserialize)]
 pub struct TorHiddenServiceStatus {
     pub hostname: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conditions: Vec<TorHiddenServiceCondition>,
+}
+
+#[derive(Clone, Debug, PartialEq, schemars::JsonSchema, serde::Serialize, serde::Deserialize)]
+pub struct TorHiddenServiceCondition {
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub status: String,
+    pub reason: String,
+    pub message: String,
+}
+
+/// Number of lines of Tor output quoted in a condition message.
+const MESSAGE_LINES: usize = 10;
+
+impl TorHiddenServiceCondition {
+    /// Describes whether Tor is degraded, quoting its last lines of output when it exited.
+    pub fn degraded(tor: &Status) -> Self {
+        let (status, reason) = match tor.state {
+            State::Pending | State::Starting => ("Unknown", "TorStarting"),
+            State::Running | State::Reloading => ("False", "TorRunning"),
+            State::Restarting => ("True", "TorRestarting"),
+            State::Failed => ("True", "TorFailed"),
+            State::Stopped => ("True", "TorStopped"),
+        };
+
+        let message = match (status, tor.last_exit_code) {
+            ("True", Some(code)) => format!("Tor exited with code {}.", code),
+            ("True", None) => "Tor was terminated by a signal.".to_string(),
+            _ => format!("Tor is {}.", format!("{:?}", tor.state).to_lowercase()),
+        };
+
+        let skip = tor.last_output.len().saturating_sub(MESSAGE_LINES);
+        let message = match status {
+            "True" if !tor.last_output.is_empty() => {
+                format!("{}\n{}", message, tor.last_output[skip..].join("\n"))
+            }
+            _ => message,
+        };
+
+        Self {
+            type_: "Degraded".to_string(),
+            status: status.to_string(),
+            reason: reason.to_string(),
+            message,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TorHiddenServiceCondition;
+    use tor_sub_process::{State, Status};
+
+    #[test]
+    fn degraded_when_restarting() {
+        let tor = Status {
+            state: State::Restarting,
+            restarts: 1,
+            last_exit_code: Some(1),
+            last_output: (0..12).map(|i| format!("line {}", i)).collect(),
+        };
+
+        let condition = TorHiddenServiceCondition::degraded(&tor);
+
+        assert_eq!("Degraded", condition.type_);
+        assert_eq!("True", condition.status);
+        assert_eq!("TorRestarting", condition.reason);
+        assert_eq!(
+            "Tor exited with code 1.\nline 2\nline 3\nline 4\nline 5\nline 6\nline 7\nline 8\nline 9\nline 10\nline 11",
+            condition.message
+        );
+    }
+
+    #[test]
+    fn not_degraded_when_running() {
+        let tor = Status {
+            state: State::Running,
+            ..Status::default()
+        };
+
+        let condition = TorHiddenServiceCondition::degraded(&tor);
+
+        assert_eq!("False", condition.status);
+        assert_eq!("TorRunning", condition.reason);
+        assert_eq!("Tor is running.", condition.message);
+    }
 }
@@ -1,8 +1,13 @@
-mod configuration;
+pub mod cli;
+pub mod configuration;
 mod kubernetes;
+mod reload;
 mod routes;
+pub mod shutdown;
 mod startup;
 pub mod telemetry;
+mod tor;
 
 pub use kubernetes::TorHiddenService;
-pub use startup::run;
+pub use startup::{Application, ApplicationBuilder, StartupError};
+pub use tor::Pool;
@@ -1,19 +1,50 @@
-use rust_kata_004::{run, telemetry};
+use rust_kata_004::cli::{self, Cli, Command};
+use rust_kata_004::configuration::redact;
+use rust_kata_004::{telemetry, Application};
 
 #[actix_web::main]
-async fn main() -> std::io::Result<()> {
-    telemetry::init(telemetry::configure("info"));
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = match Cli::parse(std::env::args().skip(1)) {
+        Ok(cli) => cli,
+        Err(error) => {
+            eprintln!("{}\n\n{}", error, cli::USAGE);
+            std::process::exit(2)
+        }
+    };
+    if cli.help {
+        println!("{}", cli::USAGE);
+        return Ok(());
+    }
 
-    let (server, _, drainer) = run(&[]).await;
+    let source = cli.source();
+    let configuration = source.load()?;
 
-    tokio::select! {
-        _ = drainer => {
-            println!("kubernetes controller drained.")
-        },
-        _ = server => {
-            println!("actix-web server exited.")
-        },
+    if cli.print_config {
+        print!("{}", serde_yaml::to_string(&redact(&configuration))?);
+    }
+    if cli.command == Command::CheckConfig {
+        if let Err(error) = configuration.validate() {
+            eprintln!("{}", error);
+            std::process::exit(1)
+        }
+        println!("Configuration is valid.");
+        return Ok(());
+    }
+    if cli.print_config {
+        return Ok(());
     }
 
-    Ok(())
+    configuration.validate()?;
+    let filter = telemetry::init_from(&configuration.telemetry);
+
+    let application = Application::builder()
+        .source(source)
+        .configuration(configuration)
+        .filter(filter)
+        .build()
+        .await?;
+
+    let code = application.run().await;
+
+    std::process::exit(code)
 }
@@ -0,0 +1,307 @@
+use crate::configuration::{
+    diff, Change, Configuration, ConfigurationSource, ControllerConfiguration,
+};
+use crate::telemetry::{self, FilterHandle};
+use crate::tor::Pool;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::{watch, Mutex};
+
+/// Settings applied to the running operator, every other change needs a restart.
+const SAFE: &[&str] = &[
+    "controller.error_requeue_seconds",
+    "controller.requeue_seconds",
+    "reload.debounce_milliseconds",
+    "telemetry.level",
+    "tor_pool.reload_debounce_milliseconds",
+    "tor_pool.size",
+];
+
+/// Watches the configuration directory, applying changes which do not need a restart.
+pub struct Reloader {
+    source: ConfigurationSource,
+    running: Configuration,
+    controller: watch::Sender<ControllerConfiguration>,
+    pool: Arc<Mutex<Pool>>,
+    filter: Option<FilterHandle>,
+    /// Whether a setting of the Tor pool failed to apply and is to be retried.
+    retry: bool,
+}
+
+impl Reloader {
+    pub fn new(
+        source: ConfigurationSource,
+        running: Configuration,
+        controller: watch::Sender<ControllerConfiguration>,
+        pool: Arc<Mutex<Pool>>,
+        filter: Option<FilterHandle>,
+    ) -> Self {
+        Self {
+            source,
+            running,
+            controller,
+            pool,
+            filter,
+            retry: false,
+        }
+    }
+
+    /// Polls the configuration directory and overlays, reloading once they stop changing.
+    pub async fn run(mut self) {
+        let mut snapshot = snapshot(&self.source);
+        let mut changed_at = None;
+
+        loop {
+            tokio::time::sleep(self.running.reload.interval()).await;
+
+            let current = snapshot(&self.source);
+            if current != snapshot {
+                snapshot = current;
+                changed_at = Some(Instant::now());
+                continue;
+            }
+
+            match changed_at {
+                Some(at) if at.elapsed() >= self.running.reload.debounce() => {
+                    changed_at = None;
+                    self.reload().await;
+                    if self.retry {
+                        changed_at = Some(Instant::now());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Loads the configuration again, applying safe changes and returning the rejected ones.
+    pub async fn reload(&mut self) -> Vec<Change> {
+        self.retry = false;
+        let configuration = match self.source.load() {
+            Ok(configuration) => configuration,
+            Err(error) => {
+                tracing::error!(%error, "failed to reload configuration");
+                return Vec::new();
+            }
+        };
+        if let Err(error) = configuration.validate() {
+            tracing::error!(%error, "rejected reloaded configuration");
+            return Vec::new();
+        }
+
+        let (applied, rejected): (Vec<Change>, Vec<Change>) = diff(&self.running, &configuration)
+            .into_iter()
+            .partition(|change| SAFE.contains(&change.key.as_str()));
+        for change in &applied {
+            tracing::info!("Applying configuration change {}", change);
+        }
+        for change in &rejected {
+            tracing::warn!(
+                "Rejecting configuration change {}, it requires a restart.",
+                change
+            );
+        }
+
+        if !applied.is_empty() {
+            self.apply(configuration).await;
+        }
+        rejected
+    }
+
+    /// Copies the safe settings into the running configuration and applies them. Settings of
+    /// the Tor pool which fail to apply are left unchanged, to be retried once the reload
+    /// debounce elapses again.
+    async fn apply(&mut self, configuration: Configuration) {
+        let running = &mut self.running;
+
+        if running.telemetry.level != configuration.telemetry.level {
+            running.telemetry.level = configuration.telemetry.level;
+            if let Some(filter) = &self.filter {
+                if let Err(error) = filter.reload(telemetry::filter(&running.telemetry.level)) {
+                    tracing::error!(%error, "failed to change log level");
+                }
+            }
+        }
+
+        if running.controller.requeue_seconds != configuration.controller.requeue_seconds
+            || running.controller.error_requeue_seconds
+                != configuration.controller.error_requeue_seconds
+        {
+            running.controller.requeue_seconds = configuration.controller.requeue_seconds;
+            running.controller.error_requeue_seconds =
+                configuration.controller.error_requeue_seconds;
+            // the controller may have stopped, leaving no receiver
+            let _ = self.controller.send(running.controller.clone());
+        }
+
+        running.reload.debounce_milliseconds = configuration.reload.debounce_milliseconds;
+
+        let tor_pool = &configuration.tor_pool;
+        if running.tor_pool.reload_debounce_milliseconds != tor_pool.reload_debounce_milliseconds {
+            let mut pool = self.pool.lock().await;
+            match pool.set_reload_debounce(tor_pool.reload_debounce()).await {
+                Ok(()) => {
+                    running.tor_pool.reload_debounce_milliseconds =
+                        tor_pool.reload_debounce_milliseconds
+                }
+                Err(error) => {
+                    tracing::error!(%error, "failed to change Tor reload debounce");
+                    self.retry = true;
+                }
+            }
+        }
+
+        if running.tor_pool.size != tor_pool.size {
+            // the control port needs a restart, so the size must fit the running one
+            if !running.tor_pool.control_ports_fit(tor_pool.size) {
+                tracing::error!(
+                    "Rejecting configuration change tor_pool.size: {} -> {}, the running tor_pool.control_port: {:?} leaves no valid port for each instance.",
+                    running.tor_pool.size,
+                    tor_pool.size,
+                    running.tor_pool.control_port
+                );
+            } else {
+                let mut pool = self.pool.lock().await;
+                match pool.resize(tor_pool.size).await {
+                    Ok(_) => running.tor_pool.size = tor_pool.size,
+                    Err(error) => {
+                        tracing::error!(%error, "failed to resize Tor pool");
+                        self.retry = true;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Contents of every file in the directory and of the overlays, following the symlinks of
+/// mounted ConfigMaps.
+fn snapshot(source: &ConfigurationSource) -> BTreeMap<PathBuf, Vec<u8>> {
+    std::fs::read_dir(&source.directory)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .chain(source.overlays.iter().cloned())
+        .filter(|path| path.is_file())
+        .filter_map(|path| {
+            let contents = std::fs::read(&path).ok()?;
+            Some((path, contents))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{snapshot, Reloader};
+    use crate::configuration::test_directory::TestDirectory;
+    use crate::configuration::{ConfigurationSource, ControllerConfiguration};
+    use crate::tor::Pool;
+    use std::sync::Arc;
+    use tokio::sync::{watch, Mutex};
+
+    fn create_directory(name: &str) -> TestDirectory {
+        TestDirectory::new(
+            &format!("reload-{}", name),
+            &["default.yaml", "local.yaml", "production.yaml"],
+            &[],
+        )
+    }
+
+    fn create_reloader(
+        source: &ConfigurationSource,
+    ) -> (Reloader, watch::Receiver<ControllerConfiguration>) {
+        let configuration = source.load().unwrap();
+        let (sender, receiver) = watch::channel(configuration.controller.clone());
+        let pool = Arc::new(Mutex::new(Pool::new(&configuration.tor_pool)));
+        let reloader = Reloader::new(source.clone(), configuration, sender, pool, None);
+        (reloader, receiver)
+    }
+
+    fn edit(source: &ConfigurationSource, from: &str, to: &str) {
+        let path = source.directory.join("default.yaml");
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(path, contents.replace(from, to)).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn reload_applies_safe_changes_and_rejects_others() {
+        let directory = create_directory("apply");
+        let source = directory.source();
+        let (mut reloader, receiver) = create_reloader(&source);
+
+        edit(&source, "requeue_seconds: 1800", "requeue_seconds: 60");
+        edit(&source, "port: 8080", "port: 9090");
+        let rejected = reloader.reload().await;
+
+        assert_eq!(60, receiver.borrow().requeue_seconds);
+        assert_eq!(60, reloader.running.controller.requeue_seconds);
+        assert_eq!(8080, reloader.running.http_server.port);
+        assert_eq!(1, rejected.len());
+        assert_eq!("http_server.port: 8080 -> 9090", rejected[0].to_string());
+    }
+
+    #[actix_rt::test]
+    async fn reload_applies_tor_reload_debounce() {
+        let directory = create_directory("debounce");
+        let source = directory.source();
+        let (mut reloader, _) = create_reloader(&source);
+
+        edit(
+            &source,
+            "reload_debounce_milliseconds: 250",
+            "reload_debounce_milliseconds: 500",
+        );
+        let rejected = reloader.reload().await;
+
+        assert!(rejected.is_empty());
+        assert_eq!(500, reloader.running.tor_pool.reload_debounce_milliseconds);
+    }
+
+    #[actix_rt::test]
+    async fn reload_rejects_size_leaving_no_control_port_for_running_pool() {
+        let directory = create_directory("control-port");
+        let source = directory.source();
+        edit(&source, "  size: 0\n", "  size: 0\n  control_port: 65535\n");
+        let (mut reloader, _) = create_reloader(&source);
+
+        edit(&source, "control_port: 65535", "control_port: 9051");
+        edit(&source, "  size: 0\n", "  size: 2\n");
+        let rejected = reloader.reload().await;
+
+        assert_eq!(1, rejected.len());
+        assert_eq!(
+            "tor_pool.control_port: 65535 -> 9051",
+            rejected[0].to_string()
+        );
+        assert_eq!(0, reloader.running.tor_pool.size);
+    }
+
+    #[actix_rt::test]
+    async fn reload_keeps_running_configuration_when_invalid() {
+        let directory = create_directory("invalid");
+        let source = directory.source();
+        let (mut reloader, receiver) = create_reloader(&source);
+
+        edit(&source, "requeue_seconds: 1800", "requeue_seconds: 0");
+        let rejected = reloader.reload().await;
+
+        assert!(rejected.is_empty());
+        assert_eq!(1800, receiver.borrow().requeue_seconds);
+    }
+
+    #[test]
+    fn snapshot_changes_with_file_contents() {
+        let directory = create_directory("snapshot");
+        let source = directory.source();
+
+        let before = snapshot(&source);
+        edit(&source, "port: 8080", "port: 9090");
+        let after = snapshot(&source);
+
+        assert_eq!(3, before.len());
+        assert_ne!(before, after);
+    }
+}
@@ -1,9 +1,63 @@
-use actix_web::HttpResponse;
+use crate::kubernetes::Manager;
+use crate::shutdown::Shutdown;
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, HttpResponse};
+
+/// Token which requests to the `/admin` routes must send as `Authorization: Bearer <token>`.
+pub struct AdminToken(pub String);
+
+impl AdminToken {
+    /// Compares the token sent with the request in constant time.
+    fn authorizes(&self, request: &HttpRequest) -> bool {
+        let sent = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .unwrap_or_default();
+        sent.len() == self.0.len()
+            && sent
+                .bytes()
+                .zip(self.0.bytes())
+                .fold(0, |difference, (a, b)| difference | (a ^ b))
+                == 0
+    }
+}
 
 pub fn health_liveness() -> HttpResponse {
     HttpResponse::Ok().finish()
 }
 
-pub fn health_readiness() -> HttpResponse {
-    HttpResponse::Ok().finish()
+pub fn health_readiness(shutdown: web::Data<Shutdown>) -> HttpResponse {
+    if shutdown.is_draining() {
+        HttpResponse::ServiceUnavailable().finish()
+    } else {
+        HttpResponse::Ok().finish()
+    }
+}
+
+pub async fn admin_tor_output(
+    request: HttpRequest,
+    token: web::Data<AdminToken>,
+    manager: web::Data<Manager>,
+) -> HttpResponse {
+    if !token.authorizes(&request) {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    let instances: Vec<serde_json::Value> = manager
+        .tor_statuses()
+        .await
+        .into_iter()
+        .map(|status| {
+            serde_json::json!({
+                "state": format!("{:?}", status.state),
+                "restarts": status.restarts,
+                "last_exit_code": status.last_exit_code,
+                "last_output": status.last_output,
+            })
+        })
+        .collect();
+
+    HttpResponse::Ok().json(serde_json::json!({ "instances": instances }))
 }
@@ -0,0 +1,142 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// Exit code when every component stopped cleanly.
+pub const EXIT_SUCCESS: i32 = 0;
+/// Exit code when a component exited unexpectedly or failed to stop.
+pub const EXIT_FAILURE: i32 = 1;
+/// Exit code when shutting down did not finish before the deadline, distinct from the exit code
+/// 2 of command line usage errors.
+pub const EXIT_TIMEOUT: i32 = 3;
+
+/// Coordinates a graceful shutdown between readiness, reconciles and the process.
+#[derive(Clone, Default)]
+pub struct Shutdown {
+    state: Arc<State>,
+}
+
+#[derive(Default)]
+struct State {
+    draining: AtomicBool,
+    in_flight: AtomicUsize,
+    idle: Notify,
+}
+
+/// Marks a reconcile as in flight until dropped.
+pub struct InFlight {
+    state: Arc<State>,
+}
+
+impl Shutdown {
+    /// Returns true once shutdown has begun, after which the operator reports it is not ready.
+    pub fn is_draining(&self) -> bool {
+        self.state.draining.load(Ordering::SeqCst)
+    }
+
+    /// Stops accepting new reconciles.
+    pub fn begin(&self) {
+        self.state.draining.store(true, Ordering::SeqCst);
+    }
+
+    /// Registers a reconcile as in flight, `None` if shutdown has begun.
+    pub fn enter(&self) -> Option<InFlight> {
+        self.state.in_flight.fetch_add(1, Ordering::SeqCst);
+        let in_flight = InFlight {
+            state: self.state.clone(),
+        };
+
+        if self.is_draining() {
+            return None;
+        }
+        Some(in_flight)
+    }
+
+    /// Begins shutdown then waits for in-flight reconciles to finish, returning false if they
+    /// did not finish within `timeout`.
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        self.begin();
+
+        tokio::time::timeout(timeout, async {
+            while self.state.in_flight.load(Ordering::SeqCst) > 0 {
+                self.state.idle.notified().await;
+            }
+        })
+        .await
+        .is_ok()
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        if self.state.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.state.idle.notify_one();
+        }
+    }
+}
+
+/// Completes with the name of the first termination signal received.
+#[cfg(target_family = "unix")]
+pub async fn signal() -> std::io::Result<&'static str> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
+
+    tokio::select! {
+        _ = terminate.recv() => Ok("SIGTERM"),
+        _ = interrupt.recv() => Ok("SIGINT"),
+    }
+}
+
+/// Completes with the name of the first termination signal received.
+#[cfg(target_family = "windows")]
+pub async fn signal() -> std::io::Result<&'static str> {
+    tokio::signal::ctrl_c().await?;
+    Ok("CTRL-C")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Shutdown;
+    use std::time::Duration;
+
+    #[actix_rt::test]
+    async fn refuses_reconciles_once_draining() {
+        let shutdown = Shutdown::default();
+
+        let before = shutdown.enter();
+        shutdown.begin();
+        let after = shutdown.enter();
+
+        assert!(before.is_some());
+        assert!(after.is_none());
+        assert!(shutdown.is_draining());
+    }
+
+    #[actix_rt::test]
+    async fn drain_waits_for_in_flight_reconciles() {
+        let shutdown = Shutdown::default();
+        let in_flight = shutdown.enter();
+
+        let finished = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            std::mem::drop(in_flight);
+        });
+        let drained = shutdown.drain(Duration::from_secs(5)).await;
+
+        assert!(drained);
+        assert!(finished.await.is_ok());
+    }
+
+    #[actix_rt::test]
+    async fn drain_gives_up_after_timeout() {
+        let shutdown = Shutdown::default();
+        let _in_flight = shutdown.enter();
+
+        let drained = shutdown.drain(Duration::from_millis(50)).await;
+
+        assert!(!drained);
+    }
+}
@@ -1,41 +1,305 @@
-use crate::configuration::Configuration;
+use crate::configuration::{Configuration, ConfigurationSource, InvalidConfiguration};
 use crate::kubernetes::Manager;
-use crate::routes::{health_liveness, health_readiness};
+use crate::reload::Reloader;
+use crate::routes::{admin_tor_output, health_liveness, health_readiness, AdminToken};
+use crate::shutdown::{self, Shutdown};
+use crate::telemetry::FilterHandle;
+use crate::tor::Pool;
 use actix_web::dev::Server;
 use actix_web::{web, App, HttpServer};
+use futures::FutureExt;
 use std::future::Future;
+use std::net::SocketAddr;
 use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{watch, Mutex};
+use tokio::time::{timeout_at, Instant};
 use tracing_actix_web::TracingLogger;
 
-pub async fn run(
-    overrides: &[(&str, &str)],
-) -> (Server, u16, Pin<Box<dyn Future<Output = ()> + Send>>) {
-    let configuration = Configuration::load(overrides).expect("Failed to read configuration.");
-
-    let listener = configuration
-        .http_server
-        .tcp_listener()
-        .expect("Failed to bind port");
-    let port = listener.local_addr().unwrap().port();
-
-    let client = kube::Client::try_default()
-        .await
-        .expect("Failed to create client.");
-    let (manager, drainer) = Manager::new(client).await;
-
-    let server = HttpServer::new(move || {
-        App::new()
-            .wrap(TracingLogger)
-            .service(
-                web::scope("/health")
-                    .route("/liveness", web::get().to(health_liveness))
-                    .route("/readiness", web::to(health_readiness)),
+/// Running operator, serving HTTP and reconciling `TorHiddenService`s once its futures are polled.
+pub struct Application {
+    pub server: Server,
+    pub address: SocketAddr,
+    pub controller: Pin<Box<dyn Future<Output = ()> + Send>>,
+    /// Applies changes to the configuration files, pending forever when reloading is disabled.
+    pub reloader: Pin<Box<dyn Future<Output = ()> + Send>>,
+    pub pool: Arc<Mutex<Pool>>,
+    pub shutdown: Shutdown,
+    pub shutdown_timeout: Duration,
+}
+
+/// Builds an `Application`, loading any dependency which was not injected.
+#[derive(Default)]
+pub struct ApplicationBuilder {
+    source: Option<ConfigurationSource>,
+    configuration: Option<Configuration>,
+    filter: Option<FilterHandle>,
+    client: Option<kube::Client>,
+    pool: Option<Pool>,
+}
+
+#[derive(Debug)]
+pub enum StartupError {
+    Configuration(config::ConfigError),
+    Invalid(InvalidConfiguration),
+    Bind(std::io::Error),
+    Client(kube::Error),
+    Tor(tor_sub_process::Error),
+}
+
+impl Application {
+    pub fn builder() -> ApplicationBuilder {
+        ApplicationBuilder::default()
+    }
+
+    /// Runs until SIGTERM or SIGINT, or until the server or controller stops, then shuts down
+    /// gracefully and returns the process exit code.
+    pub async fn run(self) -> i32 {
+        let Application {
+            server,
+            controller,
+            mut reloader,
+            pool,
+            shutdown,
+            shutdown_timeout,
+            ..
+        } = self;
+        let mut controller = controller.fuse();
+        let handle = server.clone();
+        let mut server = tokio::spawn(server);
+        let mut stopped = None;
+
+        let mut code = tokio::select! {
+            signal = shutdown::signal() => match signal {
+                Ok(signal) => {
+                    tracing::info!("Received {}, shutting down.", signal);
+                    shutdown::EXIT_SUCCESS
+                }
+                Err(error) => {
+                    tracing::error!(%error, "failed to listen for signals, shutting down");
+                    shutdown::EXIT_FAILURE
+                }
+            },
+            _ = &mut controller => {
+                tracing::error!("Kubernetes controller stopped, shutting down.");
+                shutdown::EXIT_FAILURE
+            },
+            _ = &mut reloader => {
+                tracing::error!("Configuration reloader stopped, shutting down.");
+                shutdown::EXIT_FAILURE
+            },
+            result = &mut server => {
+                stopped = Some(result);
+                tracing::error!("HTTP server stopped, shutting down.");
+                shutdown::EXIT_FAILURE
+            },
+        };
+
+        // every step of the shutdown shares the deadline
+        let deadline = Instant::now() + shutdown_timeout;
+
+        // fail readiness and let in-flight reconciles finish while the controller is still polled
+        let drained = tokio::select! {
+            drained = shutdown.drain(shutdown_timeout) => drained,
+            _ = &mut controller => true,
+        };
+        if !drained {
+            tracing::warn!(
+                "In-flight reconciles did not finish within {:?}.",
+                shutdown_timeout
+            );
+            code = code.max(shutdown::EXIT_TIMEOUT);
+        }
+        std::mem::drop(controller);
+
+        let result = match stopped {
+            Some(result) => Some(result),
+            None => timeout_at(deadline, async {
+                handle.stop(true).await;
+                server.await
+            })
+            .await
+            .ok(),
+        };
+        match result {
+            Some(Ok(Err(error))) => {
+                tracing::error!(%error, "HTTP server failed");
+                code = code.max(shutdown::EXIT_FAILURE);
+            }
+            None => {
+                tracing::warn!("HTTP server did not stop before the shutdown deadline.");
+                code = code.max(shutdown::EXIT_TIMEOUT);
+            }
+            _ => {}
+        }
+
+        match timeout_at(deadline, async { pool.lock().await.stop().await }).await {
+            Ok(Ok(())) => {}
+            Ok(Err(error)) => {
+                tracing::error!(%error, "failed to stop Tor pool");
+                code = code.max(shutdown::EXIT_FAILURE);
+            }
+            Err(_) => {
+                tracing::warn!("Tor pool did not stop before the shutdown deadline.");
+                code = code.max(shutdown::EXIT_TIMEOUT);
+            }
+        }
+
+        tracing::info!("Shut down with exit code {}.", code);
+        code
+    }
+}
+
+impl ApplicationBuilder {
+    /// Loads the configuration, and reloads it, from the source instead of the `configuration`
+    /// directory.
+    pub fn source(mut self, source: ConfigurationSource) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    /// Uses the configuration instead of loading it from the `configuration` directory.
+    pub fn configuration(mut self, configuration: Configuration) -> Self {
+        self.configuration = Some(configuration);
+        self
+    }
+
+    /// Changes the log level of the installed subscriber when the configuration is reloaded.
+    pub fn filter(mut self, filter: FilterHandle) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Uses the client instead of one inferred from the kubeconfig or in-cluster environment.
+    pub fn client(mut self, client: kube::Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Uses the Tor pool instead of one created from the configuration.
+    pub fn pool(mut self, pool: Pool) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Binds the HTTP server, starts the Tor pool and creates the controller.
+    pub async fn build(self) -> Result<Application, StartupError> {
+        let source = self.source.unwrap_or_default();
+        let configuration = match self.configuration {
+            Some(configuration) => configuration,
+            None => source.load().map_err(StartupError::Configuration)?,
+        };
+        configuration.validate().map_err(StartupError::Invalid)?;
+
+        let client = match self.client {
+            Some(client) => client,
+            None => kube::Client::try_default()
+                .await
+                .map_err(StartupError::Client)?,
+        };
+
+        let listener = configuration
+            .http_server
+            .tcp_listener()
+            .map_err(StartupError::Bind)?;
+        let address = listener.local_addr().map_err(StartupError::Bind)?;
+
+        let mut pool = self
+            .pool
+            .unwrap_or_else(|| Pool::new(&configuration.tor_pool));
+        pool.start().await.map_err(StartupError::Tor)?;
+        let pool = Arc::new(Mutex::new(pool));
+
+        let shutdown = Shutdown::default();
+        let shutdown_timeout = configuration.shutdown.timeout();
+
+        let (updates, controller_configuration) = watch::channel(configuration.controller.clone());
+        let (manager, controller) = Manager::new(
+            client,
+            controller_configuration,
+            pool.clone(),
+            shutdown.clone(),
+        )
+        .await;
+
+        let reloader = if configuration.reload.enabled {
+            Reloader::new(
+                source,
+                configuration.clone(),
+                updates,
+                pool.clone(),
+                self.filter,
             )
-            .data(manager.clone())
-    })
-    .listen(listener)
-    .expect("Failed to bind address.")
-    .run();
+            .run()
+            .boxed()
+        } else {
+            futures::future::pending().boxed()
+        };
+
+        let admin_token = configuration.http_server.admin_token.clone();
+        let server = HttpServer::new(move || {
+            // the admin routes expose Tor's output so they are only served behind a token
+            let mut admin = web::scope("/admin");
+            if let Some(token) = &admin_token {
+                admin = admin
+                    .data(AdminToken(token.clone()))
+                    .route("/tor/output", web::get().to(admin_tor_output));
+            }
+
+            App::new()
+                .wrap(TracingLogger)
+                .service(
+                    web::scope("/health")
+                        .route("/liveness", web::get().to(health_liveness))
+                        .route("/readiness", web::to(health_readiness)),
+                )
+                .service(admin)
+                .data(manager.clone())
+                .data(shutdown.clone())
+        })
+        .disable_signals()
+        .shutdown_timeout(shutdown_timeout.as_secs())
+        .listen(listener)
+        .map_err(StartupError::Bind)?
+        .run();
+
+        Ok(Application {
+            server,
+            address,
+            controller,
+            reloader,
+            pool,
+            shutdown,
+            shutdown_timeout,
+        })
+    }
+}
+
+impl std::error::Error for StartupError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StartupError::Configuration(error) => Some(error),
+            StartupError::Invalid(error) => Some(error),
+            StartupError::Bind(error) => Some(error),
+            StartupError::Client(error) => Some(error),
+            StartupError::Tor(error) => Some(error),
+        }
+    }
+}
 
-    (server, port, drainer)
+impl std::fmt::Display for StartupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StartupError::Configuration(error) => {
+                write!(f, "Failed to read configuration: {}", error)
+            }
+            StartupError::Invalid(error) => write!(f, "{}", error),
+            StartupError::Bind(error) => write!(f, "Failed to bind HTTP server: {}", error),
+            StartupError::Client(error) => {
+                write!(f, "Failed to create Kubernetes client: {}", error)
+            }
+            StartupError::Tor(error) => write!(f, "Failed to start Tor pool: {}", error),
+        }
+    }
 }
@@ -1,12 +1,13 @@
 use tracing::Subscriber;
 use tracing_subscriber::fmt::time::ChronoUtc;
 use tracing_subscriber::layer::SubscriberExt;
-use tracing_subscriber::{fmt, EnvFilter, Registry};
+use tracing_subscriber::{fmt, reload, EnvFilter, Registry};
 
-pub fn configure(level: &str) -> impl Subscriber + Send + Sync {
-    let filter_layer = EnvFilter::try_from_default_env()
-        .or_else(|_| EnvFilter::try_new(level))
-        .unwrap();
+/// Handle replacing the filter of a configured subscriber.
+pub type FilterHandle = reload::Handle<EnvFilter, Registry>;
+
+pub fn configure(level: &str) -> (impl Subscriber + Send + Sync, FilterHandle) {
+    let (filter_layer, handle) = reload::Layer::new(filter(level));
 
     let fmt_layer = fmt::layer()
         .with_target(true)
@@ -15,5 +16,30 @@ pub fn configure(level: &str) -> impl Subscriber + Send + Sync {
         .with_timer(ChronoUtc::rfc3339())
         .json();
 
-    Registry::default().with(filter_layer).with(fmt_layer)
+    (
+        Registry::default().with(filter_layer).with(fmt_layer),
+        handle,
+    )
+}
+
+/// Configures human readable output, for local development.
+pub fn configure_pretty(level: &str) -> (impl Subscriber + Send + Sync, FilterHandle) {
+    let (filter_layer, handle) = reload::Layer::new(filter(level));
+
+    let fmt_layer = fmt::layer()
+        .with_target(true)
+        .with_timer(ChronoUtc::rfc3339())
+        .pretty();
+
+    (
+        Registry::default().with(filter_layer).with(fmt_layer),
+        handle,
+    )
+}
+
+/// Filter from `RUST_LOG`, falling back to `level`.
+pub fn filter(level: &str) -> EnvFilter {
+    EnvFilter::try_from_default_env()
+        .or_else(|_| EnvFilter::try_new(level))
+        .unwrap()
 }
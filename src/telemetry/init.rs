@@ -1,5 +1,23 @@
+use super::{configure, configure_pretty, FilterHandle};
+use crate::configuration::{LogFormat, TelemetryConfiguration};
 use tracing::{subscriber, Subscriber};
 
 pub fn init(subscriber: impl Subscriber + Send + Sync) {
     subscriber::set_global_default(subscriber).expect("setting tracing default failed.");
 }
+
+/// Installs the subscriber selected by the telemetry configuration.
+pub fn init_from(configuration: &TelemetryConfiguration) -> FilterHandle {
+    match configuration.format {
+        LogFormat::Json => {
+            let (subscriber, handle) = configure(&configuration.level);
+            init(subscriber);
+            handle
+        }
+        LogFormat::Pretty => {
+            let (subscriber, handle) = configure_pretty(&configuration.level);
+            init(subscriber);
+            handle
+        }
+    }
+}
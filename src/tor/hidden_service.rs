@@ -0,0 +1,39 @@
+use std::path::Path;
+
+/// Hidden service forwarding connections to a target.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HiddenService {
+    pub host: String,
+    pub port: u16,
+}
+
+impl HiddenService {
+    /// Renders the torrc lines publishing the hidden service from `directory`.
+    pub fn render(&self, directory: &Path) -> String {
+        format!(
+            "HiddenServiceDir {}\nHiddenServicePort 80 {}:{}\n",
+            directory.display(),
+            self.host,
+            self.port
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HiddenService;
+    use std::path::Path;
+
+    #[test]
+    fn render() {
+        let hidden_service = HiddenService {
+            host: "127.0.0.1".to_string(),
+            port: 8080,
+        };
+
+        assert_eq!(
+            "HiddenServiceDir hidden_services/default_test\nHiddenServicePort 80 127.0.0.1:8080\n",
+            hidden_service.render(Path::new("hidden_services/default_test"))
+        );
+    }
+}
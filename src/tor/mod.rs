@@ -0,0 +1,6 @@
+mod hidden_service;
+mod pool;
+mod sharding;
+
+pub use hidden_service::HiddenService;
+pub use pool::{Move, Pool};
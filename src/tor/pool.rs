@@ -0,0 +1,506 @@
+use super::hidden_service::HiddenService;
+use super::sharding::shard;
+use crate::configuration::TorPoolConfiguration;
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tor_sub_process::{Command, ControlPort, Controller, Error, PendingReload, Status};
+
+/// Hidden service reassigned to another Tor instance by a resize.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Move {
+    pub key: String,
+    pub from: Option<usize>,
+    pub to: Option<usize>,
+}
+
+/// Tor instance supervised by the pool.
+struct Instance {
+    controller: Controller,
+    directory: PathBuf,
+}
+
+/// Pool of Tor instances sharing the hidden services between them.
+///
+/// Each instance has its own directory holding its PID file, torrc and data directory. Hidden
+/// service directories are shared by the pool so a hidden service keeps its onion address when
+/// it is reassigned to another instance.
+///
+/// Adding or removing a hidden service only queues the reload of its instance, so the pool need
+/// not be held while the reload is debounced and Tor verifies the torrc.
+pub struct Pool {
+    program: String,
+    directory: PathBuf,
+    control_port: Option<u16>,
+    torrc: Option<PathBuf>,
+    reload_debounce: Duration,
+    size: usize,
+    instances: Vec<Instance>,
+    services: BTreeMap<String, HiddenService>,
+    /// Instances whose last reload failed, reloaded again even if their services are unchanged.
+    invalid: BTreeSet<usize>,
+}
+
+impl Pool {
+    pub fn new(configuration: &TorPoolConfiguration) -> Self {
+        Self {
+            program: configuration.program.clone(),
+            directory: configuration.directory.clone(),
+            control_port: configuration.control_port,
+            torrc: configuration.torrc.clone(),
+            reload_debounce: configuration.reload_debounce(),
+            size: configuration.size,
+            instances: Vec::new(),
+            services: BTreeMap::new(),
+            invalid: BTreeSet::new(),
+        }
+    }
+
+    /// Creates every instance, writing its torrc then starting it.
+    pub async fn start(&mut self) -> Result<(), Error> {
+        while self.instances.len() < self.size {
+            self.push_instance().await?;
+        }
+        Ok(())
+    }
+
+    /// Stops every instance concurrently, returning the first error once all of them stopped.
+    pub async fn stop(&mut self) -> Result<(), Error> {
+        let stopping = self
+            .instances
+            .iter_mut()
+            .enumerate()
+            .map(|(index, instance)| async move {
+                let stopped = instance.controller.stop().await;
+                if let Err(error) = &stopped {
+                    tracing::error!(%error, index, "failed to stop Tor instance");
+                }
+                stopped
+            });
+
+        match futures::future::join_all(stopping)
+            .await
+            .into_iter()
+            .find_map(Result::err)
+        {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    /// Changes the window over which reloads of each instance are coalesced.
+    pub async fn set_reload_debounce(&mut self, reload_debounce: Duration) -> Result<(), Error> {
+        self.reload_debounce = reload_debounce;
+        for instance in self.instances.iter_mut() {
+            instance
+                .controller
+                .set_reload_debounce(reload_debounce)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Number of instances in the pool.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Index of the instance the hidden service is assigned to, `None` if the pool is empty.
+    pub fn shard(&self, key: &str) -> Option<usize> {
+        shard(key, self.size)
+    }
+
+    /// Adds or updates a hidden service, queueing a reload of the instance it is assigned to.
+    /// Returns the pending reload, `None` if nothing changed or the pool is empty.
+    pub async fn insert(
+        &mut self,
+        key: &str,
+        service: HiddenService,
+    ) -> Result<Option<PendingReload>, Error> {
+        let index = match self.shard(key) {
+            Some(index) => index,
+            None => {
+                self.services.insert(key.to_string(), service);
+                return Ok(None);
+            }
+        };
+        if self.services.get(key) == Some(&service) && !self.invalid.contains(&index) {
+            return Ok(None);
+        }
+
+        self.services.insert(key.to_string(), service);
+        self.queue(index).await.map(Some)
+    }
+
+    /// Removes a hidden service, queueing a reload of the instance it was assigned to. Returns
+    /// the pending reload, `None` if nothing changed or the pool is empty.
+    pub async fn remove(&mut self, key: &str) -> Result<Option<PendingReload>, Error> {
+        let index = match self.shard(key) {
+            Some(index) => index,
+            None => {
+                self.services.remove(key);
+                return Ok(None);
+            }
+        };
+        if self.services.remove(key).is_none() && !self.invalid.contains(&index) {
+            return Ok(None);
+        }
+
+        self.queue(index).await.map(Some)
+    }
+
+    /// Marks the instance the hidden service is assigned to as failing to reload, so adding or
+    /// removing it again reloads the instance even though the pool is unchanged.
+    pub fn invalidate(&mut self, key: &str) {
+        if let Some(index) = self.shard(key) {
+            self.invalid.insert(index);
+        }
+    }
+
+    /// Grows or shrinks the pool to `size` instances, reassigning hidden services.
+    ///
+    /// Instances losing hidden services are reloaded, or stopped, before instances gaining them
+    /// are reloaded, or started, so no hidden service directory is used by two instances. The
+    /// size is kept if any step fails, so resizing again retries every step.
+    pub async fn resize(&mut self, size: usize) -> Result<Vec<Move>, Error> {
+        let current = self.size;
+        if size == current {
+            return Ok(Vec::new());
+        }
+
+        // torrcs are rendered for the new size while the hidden services are reassigned
+        self.size = size;
+        let resized = self.reassign(current, size).await;
+        if resized.is_err() {
+            self.size = current;
+        }
+        resized
+    }
+
+    /// Reassigns the hidden services of a pool of `current` instances to `size` instances.
+    async fn reassign(&mut self, current: usize, size: usize) -> Result<Vec<Move>, Error> {
+        let moves: Vec<Move> = self
+            .services
+            .keys()
+            .map(|key| Move {
+                key: key.clone(),
+                from: shard(key, current),
+                to: shard(key, size),
+            })
+            .filter(|moved| moved.from != moved.to)
+            .collect();
+
+        tracing::info!(
+            from = current,
+            to = size,
+            moved = moves.len(),
+            "resizing Tor pool"
+        );
+
+        while self.instances.len() > size {
+            if let Some(mut instance) = self.instances.pop() {
+                instance.controller.stop().await?;
+            }
+        }
+        self.invalid.retain(|&index| index < size);
+
+        for index in sources(&moves) {
+            if index < size {
+                self.reload(index).await?;
+            }
+        }
+
+        while self.instances.len() < size {
+            self.push_instance().await?;
+        }
+
+        for index in destinations(&moves) {
+            if index < current {
+                self.reload(index).await?;
+            }
+        }
+
+        Ok(moves)
+    }
+
+    /// Returns a snapshot of the instance the hidden service is assigned to.
+    pub fn status(&self, key: &str) -> Option<Status> {
+        let index = self.shard(key)?;
+        Some(self.instances.get(index)?.controller.status())
+    }
+
+    /// Onion address of the hidden service, `None` until Tor has published it.
+    pub fn hostname(&self, key: &str) -> Option<String> {
+        self.shard(key)?;
+        let path = self.hidden_service_directory(key).join("hostname");
+        let hostname = std::fs::read_to_string(path).ok()?;
+        Some(hostname.trim().to_string())
+    }
+
+    /// Returns a snapshot of every instance.
+    pub fn statuses(&self) -> Vec<Status> {
+        self.instances
+            .iter()
+            .map(|instance| instance.controller.status())
+            .collect()
+    }
+
+    /// Creates the controller of the instance at `index` without starting it.
+    fn instance(&self, index: usize) -> Result<Instance, Error> {
+        let directory = self.directory.join(format!("instance-{}", index));
+
+        let mut command = Command::new(&self.program, false);
+        command
+            .torrc(directory.join("torrc"))
+            .data_directory(directory.join("data"));
+        if let Some(control_port) = self.control_port {
+            let port = u16::try_from(index)
+                .ok()
+                .and_then(|index| control_port.checked_add(index))
+                .ok_or_else(|| {
+                    Error::InvalidCommand(format!(
+                        "No control port is left for Tor instance {} after {}.",
+                        index, control_port
+                    ))
+                })?;
+            command.control_port(ControlPort::Port(port));
+        }
+
+        let controller = Controller::builder(command)
+            .pid(&directory.join("tor.pid").to_string_lossy())
+            .reload_debounce(self.reload_debounce)
+            .build();
+
+        Ok(Instance {
+            controller,
+            directory,
+        })
+    }
+
+    /// Creates the instance following the last one then starts it, leaving it out of the pool
+    /// if it fails to start.
+    async fn push_instance(&mut self) -> Result<(), Error> {
+        let index = self.instances.len();
+        let instance = self.instance(index)?;
+        self.instances.push(instance);
+        if let Err(error) = self.start_instance(index).await {
+            self.instances.pop();
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    /// Writes the torrc of the instance at `index` then starts it.
+    async fn start_instance(&mut self, index: usize) -> Result<(), Error> {
+        let torrc = self.render(index);
+        let instance = self.instances.get_mut(index).ok_or(Error::NotRunning)?;
+        std::fs::create_dir_all(&instance.directory)?;
+        std::fs::write(instance.directory.join("torrc"), torrc)?;
+        instance.controller.start().await
+    }
+
+    /// Verifies and swaps in the torrc of the instance at `index`, reloading it.
+    async fn reload(&mut self, index: usize) -> Result<u64, Error> {
+        self.queue(index).await?.wait().await
+    }
+
+    /// Writes the candidate torrc of the instance at `index` then queues its reload.
+    async fn queue(&mut self, index: usize) -> Result<PendingReload, Error> {
+        let torrc = self.render(index);
+        // a failed resize may leave the instance out of the pool
+        let instance = self.instances.get(index).ok_or(Error::NotRunning)?;
+        let candidate = instance.directory.join("torrc.candidate");
+        // the event loop may be reading the previous candidate
+        let temporary = instance.directory.join("torrc.candidate.tmp");
+        std::fs::write(&temporary, torrc)?;
+        std::fs::rename(&temporary, &candidate)?;
+        let pending = instance.controller.queue_reload_config(&candidate).await?;
+        // the queued candidate includes every change to the instance's services
+        self.invalid.remove(&index);
+        Ok(pending)
+    }
+
+    /// Renders the torrc of the instance at `index` with the hidden services assigned to it.
+    fn render(&self, index: usize) -> String {
+        let size = self.size;
+        let include = self
+            .torrc
+            .iter()
+            .map(|torrc| format!("%include {}\n", torrc.display()));
+        let services = self
+            .services
+            .iter()
+            .filter(|(key, _)| shard(key, size) == Some(index))
+            .map(|(key, service)| service.render(&self.hidden_service_directory(key)));
+        include.chain(services).collect()
+    }
+
+    /// Directory of a hidden service, shared by every instance.
+    fn hidden_service_directory(&self, key: &str) -> PathBuf {
+        self.directory
+            .join("hidden_services")
+            .join(Path::new(&key.replace('/', "_")))
+    }
+}
+
+/// Instances losing hidden services, in order.
+fn sources(moves: &[Move]) -> Vec<usize> {
+    let mut indexes: Vec<usize> = moves.iter().filter_map(|moved| moved.from).collect();
+    indexes.sort_unstable();
+    indexes.dedup();
+    indexes
+}
+
+/// Instances gaining hidden services, in order.
+fn destinations(moves: &[Move]) -> Vec<usize> {
+    let mut indexes: Vec<usize> = moves.iter().filter_map(|moved| moved.to).collect();
+    indexes.sort_unstable();
+    indexes.dedup();
+    indexes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::test_directory::TestDirectory;
+
+    fn create_pool(size: usize) -> Pool {
+        Pool::new(&TorPoolConfiguration {
+            size,
+            program: "tor".to_string(),
+            directory: PathBuf::from("tor"),
+            control_port: None,
+            torrc: None,
+            reload_debounce_milliseconds: 0,
+        })
+    }
+
+    fn create_service(port: u16) -> HiddenService {
+        HiddenService {
+            host: "127.0.0.1".to_string(),
+            port,
+        }
+    }
+
+    #[actix_rt::test]
+    async fn empty_pool_keeps_services_unassigned() {
+        let mut pool = create_pool(0);
+
+        let pending = pool.insert("default/test", create_service(8080)).await;
+
+        assert!(pending.unwrap().is_none());
+        assert_eq!(None, pool.shard("default/test"));
+        assert_eq!(None, pool.status("default/test"));
+        assert_eq!(None, pool.hostname("default/test"));
+    }
+
+    #[actix_rt::test]
+    async fn invalidated_instance_is_reloaded_even_if_unchanged() {
+        let mut pool = create_pool(1);
+        pool.services
+            .insert("default/test".to_string(), create_service(8080));
+
+        let unchanged = pool.insert("default/test", create_service(8080)).await;
+        pool.invalidate("default/test");
+        let invalidated = pool.insert("default/test", create_service(8080)).await;
+
+        assert!(unchanged.unwrap().is_none());
+        // the instance is not started so the reload cannot be queued
+        assert!(invalidated.is_err());
+        assert!(pool.invalid.contains(&0));
+    }
+
+    #[actix_rt::test]
+    async fn failed_resize_keeps_size_and_can_be_retried() {
+        let directory = TestDirectory::new("pool-resize", &[], &[]);
+        let mut pool = create_pool(0);
+        pool.program = std::env::current_dir()
+            .unwrap()
+            .join("target/debug/tor-stub")
+            .to_string_lossy()
+            .to_string();
+        pool.directory = directory.path.clone();
+        // a file in place of the instance directory fails the start
+        let blocked = directory.path.join("instance-0");
+        std::fs::write(&blocked, "").unwrap();
+
+        let failed = pool.resize(1).await;
+        let size = pool.size();
+        let statuses = pool.statuses().len();
+        std::fs::remove_file(&blocked).unwrap();
+        let retried = pool.resize(1).await;
+
+        assert!(failed.is_err());
+        assert_eq!(0, size);
+        assert_eq!(0, statuses);
+        assert!(retried.is_ok());
+        assert_eq!(1, pool.size());
+        assert_eq!(1, pool.statuses().len());
+        pool.stop().await.unwrap();
+    }
+
+    #[test]
+    fn instance_fails_if_control_port_overflows() {
+        let mut pool = create_pool(2);
+        pool.control_port = Some(u16::MAX);
+
+        assert!(pool.instance(0).is_ok());
+        assert!(matches!(pool.instance(1), Err(Error::InvalidCommand(_))));
+    }
+
+    #[test]
+    fn render_includes_only_assigned_services() {
+        let mut pool = create_pool(2);
+        for i in 0..10 {
+            pool.services
+                .insert(format!("default/service-{}", i), create_service(8080 + i));
+        }
+
+        let rendered = [pool.render(0), pool.render(1)].concat();
+
+        assert_eq!(10, rendered.matches("HiddenServiceDir").count());
+        for i in 0..10 {
+            let directory = Path::new("tor")
+                .join("hidden_services")
+                .join(format!("default_service-{}", i));
+            assert!(rendered.contains(&format!("HiddenServiceDir {}\n", directory.display())));
+        }
+    }
+
+    #[test]
+    fn render_includes_base_torrc_first() {
+        let mut pool = create_pool(1);
+        pool.torrc = Some(PathBuf::from("base.torrc"));
+        pool.services
+            .insert("default/service".to_string(), create_service(8080));
+
+        let rendered = pool.render(0);
+
+        assert!(rendered.starts_with("%include base.torrc\nHiddenServiceDir "));
+    }
+
+    #[test]
+    fn sources_and_destinations_are_distinct_and_ordered() {
+        let moves = vec![
+            Move {
+                key: "a".to_string(),
+                from: Some(1),
+                to: Some(2),
+            },
+            Move {
+                key: "b".to_string(),
+                from: Some(0),
+                to: Some(2),
+            },
+            Move {
+                key: "c".to_string(),
+                from: Some(1),
+                to: None,
+            },
+        ];
+
+        assert_eq!(vec![0, 1], sources(&moves));
+        assert_eq!(vec![2], destinations(&moves));
+    }
+}
@@ -0,0 +1,67 @@
+/// Assigns a key to one of `shards` shards, `None` if there are no shards.
+///
+/// Uses jump consistent hashing so when the number of shards changes only the keys assigned to
+/// added or removed shards move. The key is hashed with FNV-1a, which unlike `DefaultHasher` is
+/// stable across Rust releases.
+pub fn shard(key: &str, shards: usize) -> Option<usize> {
+    if shards == 0 {
+        return None;
+    }
+
+    Some(jump(fnv1a(key), shards as i64) as usize)
+}
+
+/// FNV-1a 64-bit hash.
+fn fnv1a(key: &str) -> u64 {
+    key.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Jump consistent hash, see <https://arxiv.org/abs/1406.2294>.
+fn jump(mut key: u64, buckets: i64) -> i64 {
+    let mut bucket = -1;
+    let mut next = 0;
+
+    while next < buckets {
+        bucket = next;
+        key = key.wrapping_mul(2_862_933_555_777_941_757).wrapping_add(1);
+        next = ((bucket + 1) as f64 * ((1_i64 << 31) as f64 / ((key >> 33) + 1) as f64)) as i64;
+    }
+
+    bucket
+}
+
+#[cfg(test)]
+mod tests {
+    use super::shard;
+
+    #[test]
+    fn shard_is_none_without_shards() {
+        assert_eq!(None, shard("default/test", 0));
+    }
+
+    #[test]
+    fn shard_is_within_range() {
+        for i in 0..100 {
+            let key = format!("default/service-{}", i);
+            assert!(shard(&key, 3).unwrap() < 3);
+        }
+    }
+
+    #[test]
+    fn shard_is_stable() {
+        assert_eq!(shard("default/test", 5), shard("default/test", 5));
+        assert_eq!(Some(0), shard("default/test", 1));
+    }
+
+    #[test]
+    fn growing_only_moves_keys_to_new_shard() {
+        for i in 0..100 {
+            let key = format!("default/service-{}", i);
+            let before = shard(&key, 3).unwrap();
+            let after = shard(&key, 4).unwrap();
+            assert!(before == after || after == 3);
+        }
+    }
+}
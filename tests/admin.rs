@@ -0,0 +1,50 @@
+mod api_server;
+mod server;
+
+use crate::server::TestServer;
+use reqwest::Client;
+
+#[actix_rt::test]
+async fn admin_tor_output_is_not_served_without_a_token() {
+    let server = TestServer::spawn(&[]).await;
+    let client = Client::new();
+
+    let response = client
+        .get(&format!("{}/admin/tor/output", server.address))
+        .send()
+        .await
+        .expect("Failed to send request.");
+
+    assert_eq!(404, response.status().as_u16());
+}
+
+#[actix_rt::test]
+async fn admin_tor_output_rejects_requests_without_the_token() {
+    let server = TestServer::spawn(&[("http_server.admin_token", "token")]).await;
+    let client = Client::new();
+
+    for authorization in &[None, Some("Bearer other"), Some("token")] {
+        let mut request = client.get(&format!("{}/admin/tor/output", server.address));
+        if let Some(authorization) = authorization {
+            request = request.header("Authorization", *authorization);
+        }
+        let response = request.send().await.expect("Failed to send request.");
+
+        assert_eq!(401, response.status().as_u16());
+    }
+}
+
+#[actix_rt::test]
+async fn admin_tor_output_works_with_the_token() {
+    let server = TestServer::spawn(&[("http_server.admin_token", "token")]).await;
+    let client = Client::new();
+
+    let response = client
+        .get(&format!("{}/admin/tor/output", server.address))
+        .bearer_auth("token")
+        .send()
+        .await
+        .expect("Failed to send request.");
+
+    assert_eq!(200, response.status().as_u16());
+}
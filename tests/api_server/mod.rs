@@ -0,0 +1,483 @@
+#![allow(dead_code)]
+
+use actix_web::http::StatusCode;
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+use futures::StreamExt;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+const TOR_HIDDEN_SERVICES: &str = "apis/agabani.rust-kata-004/v1/torhiddenservices";
+
+/// In-memory Kubernetes API server serving list, watch, get, create, replace, patch and delete
+/// for any resource, including the `status` subresource.
+pub struct FakeApiServer {
+    pub address: String,
+    store: Arc<Mutex<Store>>,
+}
+
+/// Objects keyed by resource, e.g. `api/v1/secrets`, namespace and name.
+#[derive(Default)]
+struct Store {
+    resource_version: u64,
+    objects: BTreeMap<(String, String, String), Value>,
+    history: Vec<WatchEvent>,
+    events: Option<broadcast::Sender<WatchEvent>>,
+}
+
+#[derive(Clone)]
+struct WatchEvent {
+    resource: String,
+    namespace: String,
+    resource_version: u64,
+    line: String,
+}
+
+/// Request path split into its resource, namespace, name and subresource.
+struct Target {
+    resource: String,
+    namespace: Option<String>,
+    name: Option<String>,
+    subresource: Option<String>,
+}
+
+impl FakeApiServer {
+    pub async fn spawn() -> Self {
+        let (events, _) = broadcast::channel(256);
+        let store = Arc::new(Mutex::new(Store {
+            events: Some(events),
+            ..Store::default()
+        }));
+
+        let listener =
+            std::net::TcpListener::bind("127.0.0.1:0").expect("Failed to bind fake API server.");
+        let port = listener.local_addr().unwrap().port();
+
+        let data = web::Data::new(store.clone());
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(data.clone())
+                .default_service(web::route().to(handle))
+        })
+        .listen(listener)
+        .expect("Failed to listen on fake API server.")
+        .run();
+        let _ = tokio::spawn(server);
+
+        Self {
+            address: format!("http://127.0.0.1:{}", port),
+            store,
+        }
+    }
+
+    /// Creates a client of the fake API server.
+    pub fn client(&self) -> kube::Client {
+        let config = kube::Config::new(self.address.parse().expect("Failed to parse address."));
+        kube::Client::try_from(config).expect("Failed to create client.")
+    }
+
+    /// Creates or replaces an object, notifying watchers.
+    pub fn apply(&self, resource: &str, namespace: &str, object: Value) -> Value {
+        let name = object["metadata"]["name"]
+            .as_str()
+            .expect("Object has no name.")
+            .to_string();
+        let mut store = self.store.lock().unwrap();
+        let event = match store.get(resource, namespace, &name) {
+            Some(_) => "MODIFIED",
+            None => "ADDED",
+        };
+        store.put(resource, namespace, &name, object, event)
+    }
+
+    /// Returns an object, `None` if it does not exist.
+    pub fn get(&self, resource: &str, namespace: &str, name: &str) -> Option<Value> {
+        self.store.lock().unwrap().get(resource, namespace, name)
+    }
+
+    /// Returns every object of a resource in the namespace.
+    pub fn list(&self, resource: &str, namespace: &str) -> Vec<Value> {
+        self.store.lock().unwrap().list(resource, Some(namespace))
+    }
+
+    /// Creates a `TorHiddenService`.
+    pub fn create_tor_hidden_service(&self, namespace: &str, name: &str, host: &str, port: u16) {
+        self.apply(
+            TOR_HIDDEN_SERVICES,
+            namespace,
+            json!({
+                "apiVersion": "agabani.rust-kata-004/v1",
+                "kind": "TorHiddenService",
+                "metadata": { "name": name },
+                "spec": { "name": name, "host": host, "port": port }
+            }),
+        );
+    }
+
+    /// Waits for the status of a `TorHiddenService` to satisfy `predicate`, returning it.
+    pub async fn wait_for_tor_hidden_service_status(
+        &self,
+        namespace: &str,
+        name: &str,
+        predicate: impl Fn(&Value) -> bool,
+    ) -> Value {
+        for _ in 0..100 {
+            if let Some(status) = self
+                .get(TOR_HIDDEN_SERVICES, namespace, name)
+                .map(|object| object["status"].clone())
+                .filter(|status| !status.is_null() && predicate(status))
+            {
+                return status;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!(
+            "Timed out waiting for status of TorHiddenService {}/{}.",
+            namespace, name
+        );
+    }
+}
+
+impl Store {
+    fn get(&self, resource: &str, namespace: &str, name: &str) -> Option<Value> {
+        self.objects
+            .get(&(
+                resource.to_string(),
+                namespace.to_string(),
+                name.to_string(),
+            ))
+            .cloned()
+    }
+
+    fn list(&self, resource: &str, namespace: Option<&str>) -> Vec<Value> {
+        self.objects
+            .iter()
+            .filter(|((r, n, _), _)| r == resource && namespace.map_or(true, |ns| ns == n))
+            .map(|(_, object)| object.clone())
+            .collect()
+    }
+
+    /// Stores the object with fresh metadata and records the watch event.
+    fn put(
+        &mut self,
+        resource: &str,
+        namespace: &str,
+        name: &str,
+        mut object: Value,
+        event: &str,
+    ) -> Value {
+        self.resource_version += 1;
+        let key = (
+            resource.to_string(),
+            namespace.to_string(),
+            name.to_string(),
+        );
+        let uid = match self.objects.get(&key) {
+            Some(existing) => existing["metadata"]["uid"].clone(),
+            None => json!(format!("uid-{}", self.resource_version)),
+        };
+
+        object["metadata"]["name"] = json!(name);
+        if !namespace.is_empty() {
+            object["metadata"]["namespace"] = json!(namespace);
+        }
+        object["metadata"]["uid"] = uid;
+        object["metadata"]["resourceVersion"] = json!(self.resource_version.to_string());
+        if object["metadata"]["creationTimestamp"].is_null() {
+            object["metadata"]["creationTimestamp"] = json!("2021-03-01T12:00:00Z");
+        }
+
+        if event == "DELETED" {
+            self.objects.remove(&key);
+        } else {
+            self.objects.insert(key, object.clone());
+        }
+        self.notify(resource, namespace, event, &object);
+        object
+    }
+
+    fn notify(&mut self, resource: &str, namespace: &str, event: &str, object: &Value) {
+        let event = WatchEvent {
+            resource: resource.to_string(),
+            namespace: namespace.to_string(),
+            resource_version: self.resource_version,
+            line: format!("{}\n", json!({ "type": event, "object": object })),
+        };
+        self.history.push(event.clone());
+        if let Some(events) = &self.events {
+            let _ = events.send(event);
+        }
+    }
+}
+
+impl Target {
+    /// Parses `/api/v1/...` and `/apis/group/version/...` paths.
+    fn parse(path: &str) -> Option<Self> {
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        let prefix = match segments.first() {
+            Some(&"api") => 2,
+            Some(&"apis") => 3,
+            _ => return None,
+        };
+        if segments.len() <= prefix {
+            return None;
+        }
+
+        let (namespace, rest) = match &segments[prefix..] {
+            ["namespaces", namespace, rest @ ..] if !rest.is_empty() => {
+                (Some(namespace.to_string()), rest)
+            }
+            rest => (None, rest),
+        };
+
+        let resource = format!("{}/{}", segments[..prefix].join("/"), rest[0]);
+        Some(Self {
+            resource,
+            namespace,
+            name: rest.get(1).map(|name| name.to_string()),
+            subresource: rest.get(2).map(|subresource| subresource.to_string()),
+        })
+    }
+}
+
+async fn handle(
+    request: HttpRequest,
+    body: web::Bytes,
+    store: web::Data<Arc<Mutex<Store>>>,
+) -> HttpResponse {
+    let target = match Target::parse(request.path()) {
+        Some(target) => target,
+        None => {
+            return status(
+                StatusCode::NOT_FOUND,
+                "NotFound",
+                "the server could not find the requested resource",
+            )
+        }
+    };
+    let query: BTreeMap<String, String> =
+        web::Query::<BTreeMap<String, String>>::from_query(request.query_string())
+            .map(|query| query.into_inner())
+            .unwrap_or_default();
+    let namespace = target.namespace.clone().unwrap_or_default();
+
+    match (request.method().as_str(), &target.name) {
+        ("GET", None) if query.get("watch").map(String::as_str) == Some("true") => {
+            watch(&store, &target, &query)
+        }
+        ("GET", None) => {
+            let store = store.lock().unwrap();
+            let items = store.list(&target.resource, target.namespace.as_deref());
+            HttpResponse::Ok().json(json!({
+                "apiVersion": "v1",
+                "kind": "List",
+                "metadata": { "resourceVersion": store.resource_version.to_string() },
+                "items": items
+            }))
+        }
+        ("GET", Some(name)) => match store
+            .lock()
+            .unwrap()
+            .get(&target.resource, &namespace, name)
+        {
+            Some(object) => HttpResponse::Ok().json(object),
+            None => not_found(name),
+        },
+        ("POST", None) => {
+            let object: Value = match serde_json::from_slice(&body) {
+                Ok(object) => object,
+                Err(error) => {
+                    return status(StatusCode::BAD_REQUEST, "BadRequest", &error.to_string())
+                }
+            };
+            let name = match object["metadata"]["name"].as_str() {
+                Some(name) => name.to_string(),
+                None => {
+                    return status(
+                        StatusCode::UNPROCESSABLE_ENTITY,
+                        "Invalid",
+                        "metadata.name: Required value",
+                    )
+                }
+            };
+            let mut store = store.lock().unwrap();
+            if store.get(&target.resource, &namespace, &name).is_some() {
+                return status(
+                    StatusCode::CONFLICT,
+                    "AlreadyExists",
+                    &format!("\"{}\" already exists", name),
+                );
+            }
+            HttpResponse::Created().json(store.put(
+                &target.resource,
+                &namespace,
+                &name,
+                object,
+                "ADDED",
+            ))
+        }
+        ("PUT", Some(name)) => {
+            let object: Value = match serde_json::from_slice(&body) {
+                Ok(object) => object,
+                Err(error) => {
+                    return status(StatusCode::BAD_REQUEST, "BadRequest", &error.to_string())
+                }
+            };
+            let mut store = store.lock().unwrap();
+            let event = match store.get(&target.resource, &namespace, name) {
+                Some(_) => "MODIFIED",
+                None => "ADDED",
+            };
+            HttpResponse::Ok().json(store.put(&target.resource, &namespace, name, object, event))
+        }
+        ("PATCH", Some(name)) => patch(&request, &body, &store, &target, name),
+        ("DELETE", Some(name)) => {
+            let mut store = store.lock().unwrap();
+            match store.get(&target.resource, &namespace, name) {
+                Some(object) => HttpResponse::Ok().json(store.put(
+                    &target.resource,
+                    &namespace,
+                    name,
+                    object,
+                    "DELETED",
+                )),
+                None => not_found(name),
+            }
+        }
+        _ => status(
+            StatusCode::METHOD_NOT_ALLOWED,
+            "MethodNotAllowed",
+            "the server does not allow this method on the requested resource",
+        ),
+    }
+}
+
+/// Applies a merge or server-side apply patch, creating the object if it does not exist and the
+/// patch is an apply. Patches of the `status` subresource only change the status.
+fn patch(
+    request: &HttpRequest,
+    body: &web::Bytes,
+    store: &Mutex<Store>,
+    target: &Target,
+    name: &str,
+) -> HttpResponse {
+    let content_type = request
+        .headers()
+        .get("content-type")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    if content_type.starts_with("application/json-patch+json") {
+        return status(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "UnsupportedMediaType",
+            "JSON patches are not supported",
+        );
+    }
+    let patch: Value = match serde_json::from_slice(body) {
+        Ok(patch) => patch,
+        Err(error) => return status(StatusCode::BAD_REQUEST, "BadRequest", &error.to_string()),
+    };
+
+    let namespace = target.namespace.clone().unwrap_or_default();
+    let mut store = store.lock().unwrap();
+    let (mut object, event) = match store.get(&target.resource, &namespace, name) {
+        Some(object) => (object, "MODIFIED"),
+        None if content_type.starts_with("application/apply-patch") => (json!({}), "ADDED"),
+        None => return not_found(name),
+    };
+
+    match target.subresource.as_deref() {
+        Some("status") => merge(&mut object["status"], &patch["status"]),
+        _ => merge(&mut object, &patch),
+    }
+
+    HttpResponse::Ok().json(store.put(&target.resource, &namespace, name, object, event))
+}
+
+/// Streams the events after the requested resource version, then every new event.
+fn watch(store: &Mutex<Store>, target: &Target, query: &BTreeMap<String, String>) -> HttpResponse {
+    let since: u64 = query
+        .get("resourceVersion")
+        .and_then(|version| version.parse().ok())
+        .unwrap_or_default();
+    let resource = target.resource.clone();
+    let namespace = target.namespace.clone();
+    let matches = move |event: &WatchEvent| {
+        event.resource == resource && namespace.as_ref().map_or(true, |ns| *ns == event.namespace)
+    };
+
+    let store = store.lock().unwrap();
+    let receiver = store.events.as_ref().unwrap().subscribe();
+    let replayed: Vec<WatchEvent> = store
+        .history
+        .iter()
+        .filter(|event| event.resource_version > since && matches(event))
+        .cloned()
+        .collect();
+
+    let replayed = futures::stream::iter(replayed);
+    let live = futures::stream::unfold((receiver, matches), |(mut receiver, matches)| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) if matches(&event) => return Some((event, (receiver, matches))),
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+    let stream = replayed
+        .chain(live)
+        .map(|event| Ok::<_, actix_web::Error>(web::Bytes::from(event.line)));
+
+    HttpResponse::Ok()
+        .content_type("application/json")
+        .streaming(Box::pin(stream))
+}
+
+/// Merges `patch` into `target` as in RFC 7386, where `null` removes a field.
+fn merge(target: &mut Value, patch: &Value) {
+    let patch = match patch.as_object() {
+        Some(patch) => patch,
+        None => {
+            *target = patch.clone();
+            return;
+        }
+    };
+
+    if !target.is_object() {
+        *target = json!({});
+    }
+    if let Some(target) = target.as_object_mut() {
+        for (key, value) in patch {
+            if value.is_null() {
+                target.remove(key);
+            } else {
+                merge(target.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+fn not_found(name: &str) -> HttpResponse {
+    status(
+        StatusCode::NOT_FOUND,
+        "NotFound",
+        &format!("\"{}\" not found", name),
+    )
+}
+
+/// Responds with a Kubernetes `Status` describing the failure.
+fn status(code: StatusCode, reason: &str, message: &str) -> HttpResponse {
+    HttpResponse::build(code).json(json!({
+        "apiVersion": "v1",
+        "kind": "Status",
+        "metadata": {},
+        "status": "Failure",
+        "message": message,
+        "reason": reason,
+        "code": code.as_u16()
+    }))
+}
@@ -0,0 +1,64 @@
+mod api_server;
+mod server;
+
+use crate::server::{tor_stub, TestServer};
+use serde_json::Value;
+
+async fn spawn() -> TestServer {
+    let program = tor_stub();
+    TestServer::spawn(&[("tor_pool.size", "1"), ("tor_pool.program", &program)]).await
+}
+
+/// Waits for Tor to publish the hidden service and for its Degraded condition.
+fn published(status: &Value) -> bool {
+    status["hostname"].is_string() && status["conditions"][0]["type"] == "Degraded"
+}
+
+#[actix_rt::test]
+async fn reconcile_publishes_status() {
+    let server = spawn().await;
+
+    server
+        .api_server
+        .create_tor_hidden_service("default", "web-app", "127.0.0.1", 8080);
+    let status = server
+        .api_server
+        .wait_for_tor_hidden_service_status("default", "web-app", published)
+        .await;
+
+    assert_eq!(server.hostname("default", "web-app"), status["hostname"]);
+    assert!(status["hostname"].as_str().unwrap().ends_with(".onion"));
+    assert_eq!("False", status["conditions"][0]["status"]);
+    assert_eq!("TorRunning", status["conditions"][0]["reason"]);
+}
+
+#[actix_rt::test]
+async fn reconcile_publishes_status_in_each_namespace() {
+    let server = spawn().await;
+
+    server
+        .api_server
+        .create_tor_hidden_service("default", "web-app", "127.0.0.1", 8080);
+    let default = server
+        .api_server
+        .wait_for_tor_hidden_service_status("default", "web-app", published)
+        .await;
+    server
+        .api_server
+        .create_tor_hidden_service("other", "web-app", "127.0.0.1", 8081);
+    let other = server
+        .api_server
+        .wait_for_tor_hidden_service_status("other", "web-app", published)
+        .await;
+
+    assert_eq!(server.hostname("other", "web-app"), other["hostname"]);
+    assert_ne!(default["hostname"], other["hostname"]);
+    assert_eq!("TorRunning", other["conditions"][0]["reason"]);
+    assert_eq!(
+        1,
+        server
+            .api_server
+            .list("apis/agabani.rust-kata-004/v1/torhiddenservices", "other")
+            .len()
+    );
+}
@@ -1,3 +1,4 @@
+mod api_server;
 mod server;
 
 use crate::server::TestServer;
@@ -30,3 +31,18 @@ async fn health_check_readiness_works() {
 
     assert_eq!(200, response.status().as_u16());
 }
+
+#[actix_rt::test]
+async fn health_check_readiness_fails_once_shutting_down() {
+    let server = TestServer::spawn(&[]).await;
+    let client = Client::new();
+
+    server.shutdown.begin();
+    let response = client
+        .get(&format!("{}/health/readiness", server.address))
+        .send()
+        .await
+        .expect("Failed to send request.");
+
+    assert_eq!(503, response.status().as_u16());
+}
@@ -1,17 +1,85 @@
+#![allow(dead_code)]
+
+use crate::api_server::FakeApiServer;
+use rust_kata_004::configuration::Configuration;
+use rust_kata_004::shutdown::Shutdown;
+use rust_kata_004::Application;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Number of servers spawned by the test binary, keeping their Tor directories apart.
+static SPAWNED: AtomicUsize = AtomicUsize::new(0);
+
 pub struct TestServer {
     pub address: String,
+    pub api_server: FakeApiServer,
+    pub shutdown: Shutdown,
+    /// Directory of the Tor pool, deleted with the server.
+    pub tor_directory: PathBuf,
 }
 
 impl TestServer {
     pub async fn spawn(overrides: &[(&str, &str)]) -> Self {
-        let defaults = &[("http_server.port", "0")];
+        let tor_directory = std::env::temp_dir().join(format!(
+            "rust-kata-004-tor-{}-{}",
+            std::process::id(),
+            SPAWNED.fetch_add(1, Ordering::SeqCst)
+        ));
+        let directory = tor_directory.to_string_lossy().to_string();
+        let defaults = &[
+            ("http_server.port", "0"),
+            ("tor_pool.directory", directory.as_str()),
+        ];
+        let configuration = Configuration::load(&[defaults, overrides].concat())
+            .expect("Failed to read configuration.");
+        let api_server = FakeApiServer::spawn().await;
 
-        let (server, port, _) = rust_kata_004::run(&[defaults, overrides].concat()).await;
+        let application = Application::builder()
+            .configuration(configuration)
+            .client(api_server.client())
+            .build()
+            .await
+            .expect("Failed to build application.");
 
-        let _ = tokio::spawn(server);
+        let _ = tokio::spawn(application.server);
+        let _ = tokio::spawn(application.controller);
 
         Self {
-            address: format!("http://127.0.0.1:{}", port),
+            address: format!("http://{}", application.address),
+            api_server,
+            shutdown: application.shutdown,
+            tor_directory,
         }
     }
+
+    /// Onion address Tor published for the hidden service.
+    pub fn hostname(&self, namespace: &str, name: &str) -> String {
+        let path = self
+            .tor_directory
+            .join("hidden_services")
+            .join(format!("{}_{}", namespace, name))
+            .join("hostname");
+        std::fs::read_to_string(path)
+            .expect("Failed to read hostname.")
+            .trim()
+            .to_string()
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.tor_directory);
+    }
+}
+
+/// Path of the Tor stand-in built by the workspace.
+pub fn tor_stub() -> String {
+    let path = std::env::current_dir()
+        .unwrap()
+        .join("target/debug/tor-stub");
+
+    if !path.exists() {
+        panic!("tor-stub does not exist. Please run cargo build --workspace then try again.");
+    }
+    path.to_string_lossy().to_string()
 }
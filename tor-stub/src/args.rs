@@ -0,0 +1,182 @@
+use crate::faults::{self, Faults};
+use std::path::PathBuf;
+
+/// Command line arguments, following the conventions of Tor.
+#[derive(Debug, Default, PartialEq)]
+pub struct Args {
+    /// Configuration file, `-f`.
+    pub torrc: Option<PathBuf>,
+    /// Verifies the configuration then exits, `--verify-config`.
+    pub verify_config: bool,
+    /// Skips the delays between output, `--no-wait`.
+    pub no_wait: bool,
+    /// Prints the version then exits, `--version`.
+    pub version: bool,
+    /// Prints the hash of the password then exits, `--hash-password`.
+    pub hash_password: Option<String>,
+    /// Options overriding the configuration file, `--Option value`, or `--Option` for flags.
+    pub options: Vec<(String, String)>,
+    /// Failures to inject into the simulation.
+    pub faults: Faults,
+}
+
+impl Args {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter().peekable();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-f" => {
+                    let torrc = args
+                        .next()
+                        .ok_or("Command-line option '-f' with no value.")?;
+                    parsed.torrc = Some(PathBuf::from(torrc));
+                }
+                "--verify-config" => parsed.verify_config = true,
+                "--no-wait" => parsed.no_wait = true,
+                "--version" => parsed.version = true,
+                "--hash-password" => parsed.hash_password = Some(value(&arg, args.next())?),
+                "--exit-after" => {
+                    let value = value(&arg, args.next())?;
+                    parsed.faults.exit_after = Some(faults::parse_seconds(&arg, &value)?);
+                }
+                "--exit-code" => {
+                    let value = value(&arg, args.next())?;
+                    parsed.faults.exit_code = Some(number(&arg, &value)?);
+                }
+                "--crash-on-reload" => {
+                    let value = value(&arg, args.next())?;
+                    parsed.faults.crash_on_reload = Some(number(&arg, &value)?);
+                }
+                "--ignore-sigterm" => parsed.faults.ignore_sigterm = true,
+                "--hang-on-shutdown" => parsed.faults.hang_on_shutdown = true,
+                "--garbage-output" => parsed.faults.garbage_output = true,
+                // options such as `--quiet` take no value, so the next option is not consumed
+                option if option.starts_with("--") && option.len() > 2 => {
+                    let value = args
+                        .next_if(|next| !next.starts_with('-'))
+                        .unwrap_or_default();
+                    parsed.options.push((option[2..].to_string(), value));
+                }
+                other => return Err(format!("Unrecognized command-line option '{}'.", other)),
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    /// Returns true if invoked with arguments of Tor or injecting failures, rather than only the
+    /// stub's own flags.
+    pub fn simulate(&self) -> bool {
+        self.torrc.is_some()
+            || self.verify_config
+            || self.version
+            || self.hash_password.is_some()
+            || !self.options.is_empty()
+            || self.faults.any()
+    }
+}
+
+fn value(option: &str, value: Option<String>) -> Result<String, String> {
+    value.ok_or_else(|| format!("Command-line option '{}' with no value.", option))
+}
+
+fn number<T: std::str::FromStr>(option: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| {
+        format!(
+            "Command-line option '{}' expects a number, got '{}'.",
+            option, value
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parse_tor_arguments() {
+        let args = parse(&[
+            "--verify-config",
+            "-f",
+            "torrc",
+            "--DataDirectory",
+            "data",
+            "--Log",
+            "notice stdout",
+        ])
+        .unwrap();
+
+        assert!(args.simulate());
+        assert!(args.verify_config);
+        assert_eq!(Some(PathBuf::from("torrc")), args.torrc);
+        assert_eq!(
+            vec![
+                ("DataDirectory".to_string(), "data".to_string()),
+                ("Log".to_string(), "notice stdout".to_string())
+            ],
+            args.options
+        );
+    }
+
+    #[test]
+    fn parse_stub_arguments() {
+        let args = parse(&["--no-wait"]).unwrap();
+
+        assert!(!args.simulate());
+        assert!(args.no_wait);
+    }
+
+    #[test]
+    fn parse_fault_arguments() {
+        let args = parse(&[
+            "--exit-after",
+            "0.5",
+            "--exit-code",
+            "3",
+            "--crash-on-reload",
+            "2",
+            "--ignore-sigterm",
+        ])
+        .unwrap();
+
+        assert!(args.simulate());
+        assert!(args.options.is_empty());
+        assert_eq!(
+            Faults {
+                exit_after: Some(std::time::Duration::from_millis(500)),
+                exit_code: Some(3),
+                crash_on_reload: Some(2),
+                ignore_sigterm: true,
+                ..Faults::default()
+            },
+            args.faults
+        );
+    }
+
+    #[test]
+    fn parse_tor_flags_without_values() {
+        let args = parse(&["--quiet", "-f", "torrc", "--RunAsDaemon"]).unwrap();
+
+        assert_eq!(Some(PathBuf::from("torrc")), args.torrc);
+        assert_eq!(
+            vec![
+                ("quiet".to_string(), String::new()),
+                ("RunAsDaemon".to_string(), String::new())
+            ],
+            args.options
+        );
+    }
+
+    #[test]
+    fn parse_rejects_missing_values() {
+        assert!(parse(&["-f"]).is_err());
+        assert!(parse(&["torrc"]).is_err());
+        assert!(parse(&["--exit-code", "one"]).is_err());
+    }
+}
@@ -0,0 +1,133 @@
+use crate::hidden_service;
+use sha1::Sha1;
+use std::path::{Path, PathBuf};
+
+/// Iteration count indicator used by Tor when hashing control passwords.
+const S2K_INDICATOR: u8 = 96;
+
+/// Authentication methods accepted by the control port.
+#[derive(Clone, Debug, Default)]
+pub struct Auth {
+    pub cookie: Option<(PathBuf, Vec<u8>)>,
+    pub hashed_password: Option<String>,
+}
+
+impl Auth {
+    /// Methods in the format of `PROTOCOLINFO`.
+    pub fn methods(&self) -> String {
+        let mut methods = Vec::new();
+        if self.cookie.is_some() {
+            methods.push("COOKIE");
+        }
+        if self.hashed_password.is_some() {
+            methods.push("HASHEDPASSWORD");
+        }
+        if methods.is_empty() {
+            methods.push("NULL");
+        }
+
+        let mut line = format!("METHODS={}", methods.join(","));
+        if let Some((path, _)) = &self.cookie {
+            line.push_str(&format!(" COOKIEFILE=\"{}\"", path.display()));
+        }
+        line
+    }
+
+    /// Returns true if the secret matches the cookie or password, or no method is required.
+    pub fn authenticate(&self, secret: &[u8]) -> bool {
+        if self.cookie.is_none() && self.hashed_password.is_none() {
+            return true;
+        }
+
+        let cookie = matches!(&self.cookie, Some((_, cookie)) if cookie.as_slice() == secret);
+        let password =
+            matches!(&self.hashed_password, Some(hashed) if verify_password(hashed, secret));
+        cookie || password
+    }
+}
+
+/// Writes a cookie derived from its path, returning its contents.
+pub fn write_cookie(path: &Path) -> Result<Vec<u8>, std::io::Error> {
+    let cookie = hidden_service::bytes(&path.to_string_lossy(), "cookie", 32);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, &cookie)?;
+    Ok(cookie)
+}
+
+/// Hashes the password as `tor --hash-password` does, with a salt derived from the password.
+pub fn hash_password(password: &[u8]) -> String {
+    let mut salt = [0; 8];
+    salt.copy_from_slice(&hidden_service::bytes(
+        &String::from_utf8_lossy(password),
+        "salt",
+        8,
+    ));
+    let digest = s2k(&salt, S2K_INDICATOR, password);
+
+    format!(
+        "16:{}{}{}",
+        hex::encode_upper(salt),
+        hex::encode_upper([S2K_INDICATOR]),
+        hex::encode_upper(digest)
+    )
+}
+
+/// Verifies the password against a `HashedControlPassword`.
+pub fn verify_password(hashed: &str, password: &[u8]) -> bool {
+    let key = match hashed.strip_prefix("16:").map(hex::decode) {
+        Some(Ok(key)) if key.len() == 29 => key,
+        _ => return false,
+    };
+
+    s2k(&key[..8], key[8], password)[..] == key[9..]
+}
+
+/// Iterated and salted S2K of RFC 2440, as used by Tor.
+fn s2k(salt: &[u8], indicator: u8, password: &[u8]) -> [u8; 20] {
+    let count = (16_usize + usize::from(indicator & 15)) << (usize::from(indicator >> 4) + 6);
+    let input = [salt, password].concat();
+
+    let mut sha1 = Sha1::new();
+    let mut remaining = count;
+    while remaining > 0 {
+        let length = remaining.min(input.len());
+        sha1.update(&input[..length]);
+        remaining -= length;
+    }
+    sha1.digest().bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashed_password_verifies_only_password() {
+        let hashed = hash_password(b"password");
+
+        assert!(hashed.starts_with("16:"));
+        assert_eq!(61, hashed.len());
+        assert!(verify_password(&hashed, b"password"));
+        assert!(!verify_password(&hashed, b"passwore"));
+        assert!(!verify_password("16:00", b"password"));
+    }
+
+    #[test]
+    fn authenticate_accepts_cookie_or_password() {
+        let auth = Auth {
+            cookie: Some((PathBuf::from("control_auth_cookie"), vec![1; 32])),
+            hashed_password: Some(hash_password(b"password")),
+        };
+
+        assert!(auth.authenticate(&[1; 32]));
+        assert!(auth.authenticate(b"password"));
+        assert!(!auth.authenticate(&[2; 32]));
+        assert!(Auth::default().authenticate(b""));
+        assert_eq!(
+            "METHODS=COOKIE,HASHEDPASSWORD COOKIEFILE=\"control_auth_cookie\"",
+            auth.methods()
+        );
+    }
+}
@@ -0,0 +1,559 @@
+use crate::auth::Auth;
+use crate::hidden_service;
+use crate::log::Logger;
+use crate::simulator::VERSION;
+use crate::torrc::Config;
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tokio::sync::broadcast;
+
+/// Asynchronous events which can be subscribed to with `SETEVENTS`.
+const EVENTS: &[&str] = &["HS_DESC", "SIGNAL", "STATUS_CLIENT"];
+
+/// Bootstrap phase as `PROGRESS`, `TAG` and `SUMMARY`.
+pub type Phase = (u8, &'static str, &'static str);
+
+/// State of the simulated Tor shared with its control connections.
+pub struct Control {
+    auth: Auth,
+    term: Arc<AtomicBool>,
+    reload: Arc<AtomicBool>,
+    phase: Mutex<Phase>,
+    /// Ephemeral onion services by service ID, owned by a connection unless detached.
+    onions: Mutex<BTreeMap<String, Option<u64>>>,
+    events: broadcast::Sender<String>,
+    connections: AtomicU64,
+    keys: AtomicU64,
+}
+
+/// State of a single control connection.
+struct Session {
+    id: u64,
+    authenticated: bool,
+    events: BTreeSet<String>,
+}
+
+impl Control {
+    pub fn new(auth: Auth, term: Arc<AtomicBool>, reload: Arc<AtomicBool>) -> Arc<Self> {
+        let (events, _) = broadcast::channel(64);
+        Arc::new(Self {
+            auth,
+            term,
+            reload,
+            phase: Mutex::new((0, "starting", "Starting")),
+            onions: Mutex::new(BTreeMap::new()),
+            events,
+            connections: AtomicU64::new(0),
+            keys: AtomicU64::new(0),
+        })
+    }
+
+    /// Records the bootstrap phase, emitting a `STATUS_CLIENT` event.
+    pub fn bootstrapped(&self, phase: Phase) {
+        *self.phase.lock().unwrap() = phase;
+        self.emit(format!("STATUS_CLIENT {}", bootstrap_phase(phase)));
+    }
+
+    /// Emits a `SIGNAL` event.
+    pub fn signal(&self, signal: &str) {
+        self.emit(format!("SIGNAL {}", signal));
+    }
+
+    fn emit(&self, event: String) {
+        let _ = self.events.send(event);
+    }
+
+    /// Serves a control connection until it is closed, removing the onion services it owns.
+    async fn serve(self: Arc<Self>, stream: impl AsyncRead + AsyncWrite) {
+        let mut session = Session {
+            id: self.connections.fetch_add(1, Ordering::SeqCst),
+            authenticated: false,
+            events: BTreeSet::new(),
+        };
+        let (reader, mut writer) = tokio::io::split(stream);
+        let mut lines = BufReader::new(reader).lines();
+        let mut events = self.events.subscribe();
+
+        loop {
+            tokio::select! {
+                line = lines.next_line() => {
+                    let line = match line {
+                        Ok(Some(line)) => line,
+                        _ => break,
+                    };
+                    let (reply, close) = self.handle(&mut session, &line);
+                    if writer.write_all(reply.as_bytes()).await.is_err() || close {
+                        break;
+                    }
+                }
+                event = events.recv() => match event {
+                    Ok(event) if session.subscribed(&event) => {
+                        let event = format!("650 {}\r\n", event);
+                        if writer.write_all(event.as_bytes()).await.is_err() {
+                            break;
+                        }
+                    }
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+            }
+        }
+
+        self.onions
+            .lock()
+            .unwrap()
+            .retain(|_, owner| *owner != Some(session.id));
+    }
+
+    /// Handles a command, returning the reply and whether to close the connection.
+    fn handle(&self, session: &mut Session, line: &str) -> (String, bool) {
+        let mut parts = line.trim().splitn(2, ' ');
+        let keyword = parts.next().unwrap_or_default().to_uppercase();
+        let arguments = parts.next().unwrap_or_default().trim();
+
+        match (keyword.as_str(), session.authenticated) {
+            ("PROTOCOLINFO", _) => (self.protocol_info(), false),
+            ("AUTHENTICATE", _) => self.authenticate(session, arguments),
+            ("QUIT", _) => ("250 closing connection\r\n".to_string(), true),
+            (_, false) => ("514 Authentication required.\r\n".to_string(), true),
+            ("GETINFO", true) => (self.get_info(session, arguments), false),
+            ("SETEVENTS", true) => (set_events(session, arguments), false),
+            ("SIGNAL", true) => (self.send_signal(arguments), false),
+            ("ADD_ONION", true) => (self.add_onion(session, arguments), false),
+            ("DEL_ONION", true) => (self.del_onion(session, arguments), false),
+            (keyword, true) => (
+                format!("510 Unrecognized command \"{}\"\r\n", keyword),
+                false,
+            ),
+        }
+    }
+
+    fn protocol_info(&self) -> String {
+        format!(
+            "250-PROTOCOLINFO 1\r\n250-AUTH {}\r\n250-VERSION Tor=\"{}\"\r\n250 OK\r\n",
+            self.auth.methods(),
+            VERSION
+        )
+    }
+
+    fn authenticate(&self, session: &mut Session, arguments: &str) -> (String, bool) {
+        let secret = match arguments.strip_prefix('"') {
+            Some(quoted) => unquote(quoted).into_bytes(),
+            None => match hex::decode(arguments) {
+                Ok(secret) => secret,
+                Err(_) => {
+                    return (
+                        "551 Invalid hexadecimal encoding.  Maybe you tried a plain text \
+                         password?  If so, the standard requires that you put it in double \
+                         quotes.\r\n"
+                            .to_string(),
+                        true,
+                    )
+                }
+            },
+        };
+
+        if self.auth.authenticate(&secret) {
+            session.authenticated = true;
+            ("250 OK\r\n".to_string(), false)
+        } else {
+            (
+                "515 Authentication failed: Password did not match HashedControlPassword *or* \
+                 authentication cookie.\r\n"
+                    .to_string(),
+                true,
+            )
+        }
+    }
+
+    fn get_info(&self, session: &Session, arguments: &str) -> String {
+        let mut reply = String::new();
+
+        for key in arguments.split_whitespace() {
+            let values = match key {
+                "version" => vec![VERSION.to_string()],
+                "status/bootstrap-phase" => vec![bootstrap_phase(*self.phase.lock().unwrap())],
+                "onions/current" => self.onions_owned_by(Some(session.id)),
+                "onions/detached" => self.onions_owned_by(None),
+                _ => return format!("552 Unrecognized key \"{}\"\r\n", key),
+            };
+
+            match values.as_slice() {
+                [] => return "551 No onion services of the specified type.\r\n".to_string(),
+                [value] => reply.push_str(&format!("250-{}={}\r\n", key, value)),
+                values => {
+                    reply.push_str(&format!("250+{}=\r\n", key));
+                    for value in values {
+                        reply.push_str(&format!("{}\r\n", value));
+                    }
+                    reply.push_str(".\r\n");
+                }
+            }
+        }
+
+        reply.push_str("250 OK\r\n");
+        reply
+    }
+
+    fn onions_owned_by(&self, owner: Option<u64>) -> Vec<String> {
+        self.onions
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, onion_owner)| **onion_owner == owner)
+            .map(|(service_id, _)| service_id.clone())
+            .collect()
+    }
+
+    fn send_signal(&self, arguments: &str) -> String {
+        match arguments.to_uppercase().as_str() {
+            "RELOAD" | "HUP" => self.reload.store(true, Ordering::SeqCst),
+            "SHUTDOWN" | "HALT" | "TERM" | "INT" => self.term.store(true, Ordering::SeqCst),
+            "NEWNYM" | "CLEARDNSCACHE" | "HEARTBEAT" | "DUMP" | "DEBUG" | "ACTIVE" | "DORMANT" => {}
+            _ => return format!("552 Unrecognized signal code \"{}\"\r\n", arguments),
+        }
+        "250 OK\r\n".to_string()
+    }
+
+    /// Adds an ephemeral onion service, `ADD_ONION KeyType:KeyBlob [Flags=...] Port=...`.
+    ///
+    /// Service IDs are derived from the key blob, and new keys from a counter, so runs are
+    /// deterministic.
+    fn add_onion(&self, session: &Session, arguments: &str) -> String {
+        let mut tokens = arguments.split_whitespace();
+        let mut key = tokens.next().unwrap_or_default().splitn(2, ':');
+        let (key_blob, generated) = match (key.next(), key.next()) {
+            (Some("NEW"), Some("BEST")) | (Some("NEW"), Some("ED25519-V3")) => {
+                let n = self.keys.fetch_add(1, Ordering::SeqCst);
+                let secret = hidden_service::bytes(&format!("new-{}", n), "secret", 64);
+                (base64::encode(secret), true)
+            }
+            (Some("ED25519-V3"), Some(key_blob)) if !key_blob.is_empty() => {
+                (key_blob.to_string(), false)
+            }
+            _ => return "513 Invalid key type\r\n".to_string(),
+        };
+
+        let mut ports = 0;
+        let mut discard_pk = false;
+        let mut detach = false;
+        for token in tokens {
+            let mut parts = token.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some("Port"), Some(port)) => {
+                    let virtual_port = port.split(',').next().unwrap_or_default();
+                    if virtual_port.parse::<u16>().map_or(true, |port| port == 0) {
+                        return "512 Invalid VIRTPORT/TARGET\r\n".to_string();
+                    }
+                    ports += 1;
+                }
+                (Some("Flags"), Some(flags)) => {
+                    for flag in flags.split(',') {
+                        match flag {
+                            "DiscardPK" => discard_pk = true,
+                            "Detach" => detach = true,
+                            _ => return "512 Invalid 'Flags' argument\r\n".to_string(),
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        if ports == 0 {
+            return "512 Missing 'Port' argument\r\n".to_string();
+        }
+
+        let service_id = hidden_service::onion_address(&key_blob);
+        {
+            let mut onions = self.onions.lock().unwrap();
+            if onions.contains_key(&service_id) {
+                return "550 Onion address collision\r\n".to_string();
+            }
+            let owner = if detach { None } else { Some(session.id) };
+            onions.insert(service_id.clone(), owner);
+        }
+
+        let hsdir = format!("${}", "0".repeat(40));
+        self.emit(format!("HS_DESC UPLOAD {} UNKNOWN {}", service_id, hsdir));
+        self.emit(format!("HS_DESC UPLOADED {} UNKNOWN {}", service_id, hsdir));
+
+        let mut reply = format!("250-ServiceID={}\r\n", service_id);
+        if generated && !discard_pk {
+            reply.push_str(&format!("250-PrivateKey=ED25519-V3:{}\r\n", key_blob));
+        }
+        reply.push_str("250 OK\r\n");
+        reply
+    }
+
+    fn del_onion(&self, session: &Session, arguments: &str) -> String {
+        if arguments.is_empty() {
+            return "512 Missing argument to DEL_ONION\r\n".to_string();
+        }
+
+        let mut onions = self.onions.lock().unwrap();
+        match onions.get(arguments) {
+            Some(owner) if *owner == Some(session.id) || owner.is_none() => {
+                onions.remove(arguments);
+                "250 OK\r\n".to_string()
+            }
+            _ => "552 Unknown Onion Service id\r\n".to_string(),
+        }
+    }
+}
+
+impl Session {
+    fn subscribed(&self, event: &str) -> bool {
+        self.events
+            .contains(event.split(' ').next().unwrap_or_default())
+    }
+}
+
+/// Subscribes to the events, replacing any previous subscription.
+fn set_events(session: &mut Session, arguments: &str) -> String {
+    let mut events = BTreeSet::new();
+    for event in arguments.split_whitespace() {
+        let event = event.to_uppercase();
+        if event == "EXTENDED" {
+            continue;
+        }
+        if !EVENTS.contains(&event.as_str()) {
+            return format!("552 Unrecognized event \"{}\"\r\n", event);
+        }
+        events.insert(event);
+    }
+
+    session.events = events;
+    "250 OK\r\n".to_string()
+}
+
+fn bootstrap_phase((progress, tag, summary): Phase) -> String {
+    format!(
+        "NOTICE BOOTSTRAP PROGRESS={} TAG={} SUMMARY=\"{}\"",
+        progress, tag, summary
+    )
+}
+
+/// Unquotes the remainder of a quoted string following its opening quote.
+fn unquote(quoted: &str) -> String {
+    let mut unquoted = String::new();
+    let mut characters = quoted.chars();
+    while let Some(character) = characters.next() {
+        match character {
+            '"' => break,
+            '\\' => unquoted.extend(characters.next()),
+            character => unquoted.push(character),
+        }
+    }
+    unquoted
+}
+
+/// Opens the control listeners configured by `ControlPort` and `ControlSocket`.
+pub async fn listen(control: &Arc<Control>, config: &Config) -> Result<(), std::io::Error> {
+    if let Some(control_port) = &config.control_port {
+        let listener = TcpListener::bind(address(control_port)?).await?;
+        let address = listener.local_addr()?;
+        opened(&config.logger, &address.to_string());
+        if let Some(path) = &config.control_port_write_to_file {
+            std::fs::write(path, format!("PORT={}\n", address))?;
+        }
+
+        let control = control.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(control.clone().serve(stream));
+            }
+        });
+    }
+
+    #[cfg(target_family = "unix")]
+    listen_unix(control, config)?;
+
+    Ok(())
+}
+
+/// Opens the control listener configured by `ControlSocket`.
+#[cfg(target_family = "unix")]
+fn listen_unix(control: &Arc<Control>, config: &Config) -> Result<(), std::io::Error> {
+    if let Some(path) = &config.control_socket {
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        let listener = tokio::net::UnixListener::bind(path)?;
+        opened(&config.logger, &path.display().to_string());
+        if let Some(write_to) = &config.control_port_write_to_file {
+            std::fs::write(write_to, format!("UNIX_PORT={}\n", path.display()))?;
+        }
+
+        let control = control.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(control.clone().serve(stream));
+            }
+        });
+    }
+
+    Ok(())
+}
+
+fn opened(logger: &Logger, address: &str) {
+    logger.notice(&format!(
+        "Opened Control listener connection (ready) on {}",
+        address
+    ));
+}
+
+/// Resolves a `ControlPort` of the form `[address:]port|auto` on the loopback by default.
+fn address(control_port: &str) -> Result<SocketAddr, std::io::Error> {
+    let value = control_port.split_whitespace().next().unwrap_or_default();
+    let (host, port) = match value.rfind(':') {
+        Some(index) => (&value[..index], &value[index + 1..]),
+        None => ("127.0.0.1", value),
+    };
+    let port = match port {
+        "auto" => "0",
+        port => port,
+    };
+
+    format!("{}:{}", host, port).parse().map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Invalid ControlPort '{}'", control_port),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::duplex;
+
+    fn create_control(auth: Auth) -> Arc<Control> {
+        Control::new(
+            auth,
+            Arc::new(AtomicBool::new(false)),
+            Arc::new(AtomicBool::new(false)),
+        )
+    }
+
+    fn create_session() -> Session {
+        Session {
+            id: 0,
+            authenticated: true,
+            events: BTreeSet::new(),
+        }
+    }
+
+    #[test]
+    fn address_defaults_to_loopback() {
+        assert_eq!("127.0.0.1:9051", address("9051").unwrap().to_string());
+        assert_eq!("127.0.0.1:0", address("auto").unwrap().to_string());
+        assert_eq!("0.0.0.0:9051", address("0.0.0.0:9051").unwrap().to_string());
+        assert!(address("control").is_err());
+    }
+
+    #[test]
+    fn commands_require_authentication() {
+        let control = create_control(Auth {
+            cookie: None,
+            hashed_password: Some(crate::auth::hash_password(b"password")),
+        });
+        let mut session = create_session();
+        session.authenticated = false;
+
+        let required = control.handle(&mut session, "GETINFO version");
+        let failed = control.handle(&mut session, "AUTHENTICATE \"wrong\"");
+        let authenticated = control.handle(&mut session, "AUTHENTICATE \"password\"");
+        let version = control.handle(&mut session, "GETINFO version");
+
+        assert_eq!(
+            ("514 Authentication required.\r\n".to_string(), true),
+            required
+        );
+        assert!(failed.0.starts_with("515 ") && failed.1);
+        assert_eq!(("250 OK\r\n".to_string(), false), authenticated);
+        assert_eq!(format!("250-version={}\r\n250 OK\r\n", VERSION), version.0);
+    }
+
+    #[test]
+    fn add_onion_returns_deterministic_service_ids() {
+        let control = create_control(Auth::default());
+        let other = create_control(Auth::default());
+        let mut session = create_session();
+
+        let added = control.handle(&mut session, "ADD_ONION NEW:BEST Port=80,127.0.0.1:8080");
+        let repeated = other.handle(&mut session, "ADD_ONION NEW:BEST Port=80,127.0.0.1:8080");
+        let discarded = control.handle(
+            &mut session,
+            "ADD_ONION NEW:ED25519-V3 Flags=DiscardPK Port=80",
+        );
+        let current = control.handle(&mut session, "GETINFO onions/current");
+        let missing = control.handle(&mut session, "ADD_ONION NEW:BEST");
+
+        assert_eq!(added, repeated);
+        assert!(added.0.starts_with("250-ServiceID="));
+        assert!(added.0.contains("250-PrivateKey=ED25519-V3:"));
+        assert!(!discarded.0.contains("PrivateKey"));
+        assert!(current.0.starts_with("250+onions/current=\r\n"));
+        assert_eq!("512 Missing 'Port' argument\r\n", missing.0);
+    }
+
+    #[test]
+    fn del_onion_removes_owned_services() {
+        let control = create_control(Auth::default());
+        let mut session = create_session();
+        let mut other = create_session();
+        other.id = 1;
+        let added = control.handle(&mut session, "ADD_ONION ED25519-V3:key Port=80");
+        let service_id = added.0.lines().next().unwrap()["250-ServiceID=".len()..].to_string();
+
+        let foreign = control.handle(&mut other, &format!("DEL_ONION {}", service_id));
+        let deleted = control.handle(&mut session, &format!("DEL_ONION {}", service_id));
+        let current = control.handle(&mut session, "GETINFO onions/current");
+
+        assert_eq!("552 Unknown Onion Service id\r\n", foreign.0);
+        assert_eq!("250 OK\r\n", deleted.0);
+        assert_eq!(
+            "551 No onion services of the specified type.\r\n",
+            current.0
+        );
+    }
+
+    #[tokio::test]
+    async fn serve_emits_subscribed_events() {
+        let control = create_control(Auth::default());
+        let (client, server) = duplex(4096);
+        tokio::spawn(control.clone().serve(server));
+        let (reader, mut writer) = tokio::io::split(client);
+        let mut lines = BufReader::new(reader).lines();
+
+        writer
+            .write_all(b"AUTHENTICATE\r\nSETEVENTS SIGNAL STATUS_CLIENT\r\n")
+            .await
+            .unwrap();
+        let authenticated = lines.next_line().await.unwrap();
+        let subscribed = lines.next_line().await.unwrap();
+        control.signal("RELOAD");
+        control.bootstrapped((100, "done", "Done"));
+        let signal = lines.next_line().await.unwrap();
+        let status = lines.next_line().await.unwrap();
+        writer.write_all(b"SIGNAL SHUTDOWN\r\n").await.unwrap();
+        let shutdown = lines.next_line().await.unwrap();
+
+        assert_eq!(Some("250 OK".to_string()), authenticated);
+        assert_eq!(Some("250 OK".to_string()), subscribed);
+        assert_eq!(Some("650 SIGNAL RELOAD".to_string()), signal);
+        assert_eq!(
+            Some(
+                "650 STATUS_CLIENT NOTICE BOOTSTRAP PROGRESS=100 TAG=done SUMMARY=\"Done\""
+                    .to_string()
+            ),
+            status
+        );
+        assert_eq!(Some("250 OK".to_string()), shutdown);
+        assert!(control.term.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,69 @@
+use std::io::Write;
+use std::time::Duration;
+
+/// Failures injected into the simulation so supervisors can be tested against misbehaving Tor.
+#[derive(Debug, Default, PartialEq)]
+pub struct Faults {
+    /// Exits after the duration, `--exit-after <seconds>`.
+    pub exit_after: Option<Duration>,
+    /// Code exited with by `exit_after`, `--exit-code <code>`, defaults to 1.
+    pub exit_code: Option<i32>,
+    /// Aborts on the given reload, counting from 1, `--crash-on-reload <k>`.
+    pub crash_on_reload: Option<u32>,
+    /// Ignores SIGTERM, `--ignore-sigterm`.
+    pub ignore_sigterm: bool,
+    /// Never exits once asked to shut down, `--hang-on-shutdown`.
+    pub hang_on_shutdown: bool,
+    /// Writes invalid UTF-8 and lines which are not log lines, `--garbage-output`.
+    pub garbage_output: bool,
+}
+
+impl Faults {
+    /// Returns true if any failure is injected.
+    pub fn any(&self) -> bool {
+        *self != Self::default()
+    }
+
+    /// Code to exit with once `exit_after` elapsed.
+    pub fn exit_code(&self) -> i32 {
+        self.exit_code.unwrap_or(1)
+    }
+}
+
+/// Parses the seconds of `--exit-after`, allowing fractions.
+pub fn parse_seconds(option: &str, value: &str) -> Result<Duration, String> {
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+        .map(Duration::from_secs_f64)
+        .ok_or_else(|| {
+            format!(
+                "Command-line option '{}' expects seconds, got '{}'.",
+                option, value
+            )
+        })
+}
+
+/// Writes output which is neither valid UTF-8 nor in the format of Tor.
+pub fn write_garbage() {
+    let mut stdout = std::io::stdout();
+    let _ = stdout.write_all(b"\xff\xfe\x00garbage \x1b[31mnot a log line\n");
+    let _ = stdout.write_all(b"[unterminated severity \xc3\x28\n");
+    let _ = stdout.flush();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_seconds_allows_fractions() {
+        assert_eq!(
+            Ok(Duration::from_millis(250)),
+            parse_seconds("--exit-after", "0.25")
+        );
+        assert!(parse_seconds("--exit-after", "-1").is_err());
+        assert!(parse_seconds("--exit-after", "soon").is_err());
+    }
+}
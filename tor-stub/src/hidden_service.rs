@@ -0,0 +1,107 @@
+use std::path::Path;
+
+const BASE32: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+/// Creates the hidden service directory with its key files and `hostname`, keeping any which
+/// already exist so the onion address is stable across restarts.
+pub fn publish(directory: &Path) -> Result<String, std::io::Error> {
+    create_private_dir(directory)?;
+
+    let seed = directory.to_string_lossy();
+    let hostname = format!("{}.onion", onion_address(&seed));
+
+    write_new(
+        &directory.join("hs_ed25519_public_key"),
+        &[
+            b"== ed25519v1-public: type0 ==\0\0\0".as_ref(),
+            &bytes(&seed, "public", 32),
+        ]
+        .concat(),
+    )?;
+    write_new(
+        &directory.join("hs_ed25519_secret_key"),
+        &[
+            b"== ed25519v1-secret: type0 ==\0\0\0".as_ref(),
+            &bytes(&seed, "secret", 64),
+        ]
+        .concat(),
+    )?;
+    write_new(
+        &directory.join("hostname"),
+        format!("{}\n", hostname).as_bytes(),
+    )?;
+
+    let hostname = std::fs::read_to_string(directory.join("hostname"))?;
+    Ok(hostname.trim().to_string())
+}
+
+/// Creates a directory only accessible by its owner, as Tor requires.
+pub fn create_private_dir(directory: &Path) -> Result<(), std::io::Error> {
+    std::fs::create_dir_all(directory)?;
+
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(directory, std::fs::Permissions::from_mode(0o700))?;
+    }
+
+    Ok(())
+}
+
+/// Writes the file unless it already exists.
+fn write_new(path: &Path, contents: &[u8]) -> Result<(), std::io::Error> {
+    if path.exists() {
+        return Ok(());
+    }
+    std::fs::write(path, contents)
+}
+
+/// Derives a deterministic 56 character v3 onion address from the seed.
+pub fn onion_address(seed: &str) -> String {
+    bytes(seed, "onion", 35)
+        .chunks(5)
+        .flat_map(|chunk| {
+            let bits = chunk
+                .iter()
+                .fold(0_u64, |bits, byte| bits << 8 | u64::from(*byte));
+            (0..8)
+                .rev()
+                .map(move |i| BASE32[(bits >> (i * 5) & 31) as usize] as char)
+        })
+        .collect()
+}
+
+/// Derives deterministic bytes from the seed and purpose.
+pub fn bytes(seed: &str, purpose: &str, length: usize) -> Vec<u8> {
+    let mut state = format!("{}:{}", purpose, seed)
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+
+    (0..length)
+        .map(|_| {
+            // splitmix64
+            state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            (z ^ (z >> 31)) as u8
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn onion_address_is_deterministic() {
+        let first = onion_address("hs/first");
+
+        assert_eq!(56, first.len());
+        assert!(first.bytes().all(|byte| BASE32.contains(&byte)));
+        assert_eq!(first, onion_address("hs/first"));
+        assert_ne!(first, onion_address("hs/second"));
+    }
+}
@@ -0,0 +1,139 @@
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Severity of a log line, ordered from least to most severe.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum Severity {
+    Debug,
+    Info,
+    Notice,
+    Warn,
+    Err,
+}
+
+impl Severity {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "debug" => Some(Severity::Debug),
+            "info" => Some(Severity::Info),
+            "notice" => Some(Severity::Notice),
+            "warn" => Some(Severity::Warn),
+            "err" => Some(Severity::Err),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Debug => "debug",
+            Severity::Info => "info",
+            Severity::Notice => "notice",
+            Severity::Warn => "warn",
+            Severity::Err => "err",
+        }
+    }
+}
+
+/// Where log lines are written, configured by the `Log` option.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Logger {
+    pub minimum: Severity,
+    pub stderr: bool,
+}
+
+impl Default for Logger {
+    fn default() -> Self {
+        Self {
+            minimum: Severity::Notice,
+            stderr: false,
+        }
+    }
+}
+
+impl Logger {
+    pub fn notice(&self, message: &str) {
+        self.log(Severity::Notice, message);
+    }
+
+    pub fn warn(&self, message: &str) {
+        self.log(Severity::Warn, message);
+    }
+
+    pub fn err(&self, message: &str) {
+        self.log(Severity::Err, message);
+    }
+
+    /// Writes a line in the format of Tor, `Mar 01 12:00:00.000 [notice] message`.
+    pub fn log(&self, severity: Severity, message: &str) {
+        if severity < self.minimum {
+            return;
+        }
+
+        let line = format!("{} [{}] {}\n", timestamp(), severity.as_str(), message);
+        if self.stderr {
+            let _ = std::io::stderr().write_all(line.as_bytes());
+        } else {
+            let mut stdout = std::io::stdout();
+            let _ = stdout.write_all(line.as_bytes());
+            let _ = stdout.flush();
+        }
+    }
+}
+
+/// Formats the current UTC time as `Mar 01 12:00:00.000`.
+fn timestamp() -> String {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let seconds = now.as_secs();
+    let (_, month, day) = civil_from_days((seconds / 86_400) as i64);
+
+    format!(
+        "{} {:02} {:02}:{:02}:{:02}.{:03}",
+        MONTHS[(month - 1) as usize],
+        day,
+        seconds / 3600 % 24,
+        seconds / 60 % 60,
+        seconds % 60,
+        now.subsec_millis()
+    )
+}
+
+/// Converts days since the Unix epoch into a year, month and day.
+///
+/// See <http://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn civil_from_days_converts_dates() {
+        assert_eq!((1970, 1, 1), civil_from_days(0));
+        assert_eq!((2021, 3, 1), civil_from_days(18_687));
+        assert_eq!((2000, 2, 29), civil_from_days(11_016));
+    }
+
+    #[test]
+    fn severity_is_ordered() {
+        assert!(Severity::Notice < Severity::Warn);
+        assert_eq!(Some(Severity::Err), Severity::parse("err"));
+        assert_eq!(None, Severity::parse("fatal"));
+    }
+}
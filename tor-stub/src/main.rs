@@ -1,3 +1,13 @@
+mod args;
+mod auth;
+mod control;
+mod faults;
+mod hidden_service;
+mod log;
+mod simulator;
+mod torrc;
+
+use args::Args;
 use signal_hook::{consts, flag};
 use std::io::Write;
 use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
@@ -7,6 +17,19 @@ use tokio::time::sleep;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
+
+    if args.simulate() {
+        let code = simulator::run(args).await?;
+        std::process::exit(code);
+    }
+
     let term = Arc::new(AtomicBool::new(false));
     let reload = Arc::new(AtomicBool::new(false));
     register_shutdown_signal(term.clone())?;
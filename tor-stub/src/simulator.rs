@@ -0,0 +1,254 @@
+use crate::args::Args;
+use crate::auth::{self, Auth};
+use crate::control::{self, Control, Phase};
+use crate::faults::{self, Faults};
+use crate::hidden_service;
+use crate::log::Logger;
+use crate::torrc::{self, Config};
+use signal_hook::{consts, flag};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+
+pub const VERSION: &str = "0.4.5.6 (stub)";
+
+/// Bootstrap phases reported in the log and on the control port.
+const BOOTSTRAP: &[Phase] = &[
+    (0, "starting", "Starting"),
+    (5, "conn", "Connecting to a relay"),
+    (50, "loading_descriptors", "Loading relay descriptors"),
+    (100, "done", "Done"),
+];
+
+/// Simulates Tor, returning the exit code.
+pub async fn run(args: Args) -> Result<i32, Box<dyn std::error::Error>> {
+    if args.version {
+        println!("Tor version {}.", VERSION);
+        return Ok(0);
+    }
+
+    if let Some(password) = &args.hash_password {
+        println!("{}", auth::hash_password(password.as_bytes()));
+        return Ok(0);
+    }
+
+    let startup = Logger::default();
+    startup.notice(&format!(
+        "Tor {} running on {}.",
+        VERSION,
+        std::env::consts::OS
+    ));
+
+    let mut config = match load(&args, &startup) {
+        Some(config) => config,
+        None => return Ok(1),
+    };
+
+    if args.verify_config {
+        println!("Configuration was valid");
+        return Ok(0);
+    }
+
+    let started = Instant::now();
+    let term = Arc::new(AtomicBool::new(false));
+    let reload = Arc::new(AtomicBool::new(false));
+    register_shutdown_signal(term.clone(), &args.faults)?;
+    crate::register_reload_signal(reload.clone())?;
+
+    if !publish(&config) {
+        return Ok(1);
+    }
+    let control = match authentication(&config) {
+        Ok(auth) => Control::new(auth, term.clone(), reload.clone()),
+        Err(error) => {
+            config
+                .logger
+                .err(&format!("Error writing authentication cookie: {}", error));
+            return Ok(1);
+        }
+    };
+    if let Err(error) = control::listen(&control, &config).await {
+        config
+            .logger
+            .warn(&format!("Could not open Control listener: {}", error));
+        config
+            .logger
+            .err("Failed to bind one of the listener ports.");
+        return Ok(1);
+    }
+    bootstrap(&config, &control, args.no_wait).await;
+    if args.faults.garbage_output {
+        faults::write_garbage();
+    }
+
+    let mut reloads = 0;
+    loop {
+        if term.load(Ordering::Relaxed) {
+            config
+                .logger
+                .notice("Catching signal TERM, exiting cleanly.");
+            control.signal("SHUTDOWN");
+            if args.faults.hang_on_shutdown {
+                loop {
+                    sleep(Duration::from_secs(60)).await;
+                }
+            }
+            return Ok(0);
+        }
+
+        if let Some(exit_after) = args.faults.exit_after {
+            if started.elapsed() >= exit_after {
+                let code = args.faults.exit_code();
+                config
+                    .logger
+                    .err(&format!("Exiting with code {} as requested.", code));
+                return Ok(code);
+            }
+        }
+
+        if reload.swap(false, Ordering::SeqCst) {
+            config.logger.notice(
+                "Received reload signal (hup). Reloading config and resetting internal state.",
+            );
+            control.signal("RELOAD");
+            reloads += 1;
+            if args.faults.crash_on_reload == Some(reloads) {
+                config
+                    .logger
+                    .err(&format!("Crashing on reload {} as requested.", reloads));
+                std::process::abort();
+            }
+            match load(&args, &config.logger) {
+                Some(reloaded) if publish(&reloaded) => config = reloaded,
+                _ => {
+                    config
+                        .logger
+                        .warn("Restart failed (config error?). Exiting.");
+                    return Ok(1);
+                }
+            }
+        }
+
+        sleep(Duration::from_millis(10)).await;
+    }
+}
+
+/// Registers the termination signals, leaving out those the injected faults ignore.
+///
+/// A second termination signal normally exits immediately, unless shutdown is meant to hang.
+fn register_shutdown_signal(term: Arc<AtomicBool>, faults: &Faults) -> Result<(), std::io::Error> {
+    for &term_signal in consts::TERM_SIGNALS {
+        if faults.ignore_sigterm && term_signal == consts::SIGTERM {
+            flag::register(term_signal, Arc::new(AtomicBool::new(false)))?;
+            continue;
+        }
+        if !faults.hang_on_shutdown {
+            flag::register_conditional_shutdown(term_signal, 1, term.clone())?;
+        }
+        flag::register(term_signal, term.clone())?;
+    }
+    Ok(())
+}
+
+/// Reads and validates the configuration, logging why it is invalid as Tor does.
+fn load(args: &Args, logger: &Logger) -> Option<Config> {
+    let torrc = match &args.torrc {
+        Some(path) => match std::fs::read_to_string(path) {
+            Ok(torrc) => {
+                logger.notice(&format!("Read configuration file \"{}\".", path.display()));
+                torrc
+            }
+            Err(error) => {
+                logger.warn(&format!(
+                    "Unable to open configuration file \"{}\": {}",
+                    path.display(),
+                    error
+                ));
+                logger.err("Reading config failed--see warnings above.");
+                return None;
+            }
+        },
+        None => String::new(),
+    };
+
+    match torrc::parse(&torrc, &args.options) {
+        Ok(config) => Some(config),
+        Err(error) => {
+            logger.warn(&format!("Failed to parse/validate config: {}", error));
+            logger.err("Reading config failed--see warnings above.");
+            None
+        }
+    }
+}
+
+/// Creates the data directory and hidden service directories.
+fn publish(config: &Config) -> bool {
+    if let Some(data_directory) = &config.data_directory {
+        if let Err(error) = hidden_service::create_private_dir(data_directory) {
+            config.logger.err(&format!(
+                "Couldn't create private data directory \"{}\": {}",
+                data_directory.display(),
+                error
+            ));
+            return false;
+        }
+    }
+
+    for service in &config.hidden_services {
+        match hidden_service::publish(&service.directory) {
+            Ok(hostname) => config.logger.notice(&format!(
+                "Publishing hidden service {} from \"{}\".",
+                hostname,
+                service.directory.display()
+            )),
+            Err(error) => {
+                config.logger.warn(&format!(
+                    "Error loading rendezvous service keys from \"{}\": {}",
+                    service.directory.display(),
+                    error
+                ));
+                return false;
+            }
+        }
+    }
+
+    true
+}
+
+/// Methods accepted by the control port, writing the authentication cookie if enabled.
+fn authentication(config: &Config) -> Result<Auth, std::io::Error> {
+    let cookie = match config.cookie_path() {
+        Some(path) => {
+            let cookie = auth::write_cookie(&path)?;
+            Some((path, cookie))
+        }
+        None => None,
+    };
+
+    Ok(Auth {
+        cookie,
+        hashed_password: config.hashed_control_password.clone(),
+    })
+}
+
+/// Logs the bootstrap progress, reporting each phase to the control port.
+async fn bootstrap(config: &Config, control: &Control, no_wait: bool) {
+    if let Some(socks_port) = &config.socks_port {
+        config
+            .logger
+            .notice(&format!("Opening Socks listener on {}", socks_port));
+    }
+
+    for &phase in BOOTSTRAP {
+        let (progress, tag, summary) = phase;
+        config.logger.notice(&format!(
+            "Bootstrapped {}% ({}): {}",
+            progress, tag, summary
+        ));
+        control.bootstrapped(phase);
+        if !no_wait {
+            sleep(Duration::from_millis(50)).await;
+        }
+    }
+}
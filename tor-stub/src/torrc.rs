@@ -0,0 +1,280 @@
+use crate::log::{Logger, Severity};
+use std::path::PathBuf;
+
+/// Options which are accepted but have no effect on the stub.
+const IGNORED_OPTIONS: &[&str] = &[
+    "AvoidDiskWrites",
+    "ClientOnly",
+    "ExitPolicy",
+    "GeoIPFile",
+    "GeoIPv6File",
+    "HiddenServiceVersion",
+    "RunAsDaemon",
+    "SafeLogging",
+    "ShutdownWaitLength",
+    "User",
+];
+
+/// Hidden service published from a directory.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HiddenService {
+    pub directory: PathBuf,
+    pub ports: Vec<String>,
+}
+
+/// Configuration read from the torrc and command line.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Config {
+    pub data_directory: Option<PathBuf>,
+    pub socks_port: Option<String>,
+    pub control_port: Option<String>,
+    pub control_socket: Option<PathBuf>,
+    pub control_port_write_to_file: Option<PathBuf>,
+    pub cookie_authentication: bool,
+    pub cookie_auth_file: Option<PathBuf>,
+    pub hashed_control_password: Option<String>,
+    pub hidden_services: Vec<HiddenService>,
+    pub logger: Logger,
+}
+
+/// Parses the torrc followed by the options given on the command line, which take precedence.
+pub fn parse(torrc: &str, options: &[(String, String)]) -> Result<Config, String> {
+    let mut config = Config::default();
+
+    let lines = torrc.lines().filter_map(|line| {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            return None;
+        }
+        let mut parts = line.splitn(2, char::is_whitespace);
+        let keyword = parts.next().unwrap_or_default();
+        let value = parts.next().unwrap_or_default().trim();
+        Some((keyword.to_string(), value.to_string()))
+    });
+
+    for (keyword, value) in lines.chain(options.iter().cloned()) {
+        apply(&mut config, &keyword, &value)?;
+    }
+
+    for hidden_service in &config.hidden_services {
+        if hidden_service.ports.is_empty() {
+            return Err(format!(
+                "Configured hidden service '{}' with no ports configured.",
+                hidden_service.directory.display()
+            ));
+        }
+    }
+
+    if config.cookie_authentication
+        && config.cookie_auth_file.is_none()
+        && config.data_directory.is_none()
+    {
+        return Err("CookieAuthentication requires a DataDirectory or CookieAuthFile.".to_string());
+    }
+
+    Ok(config)
+}
+
+impl Config {
+    /// Path of the authentication cookie, written when `CookieAuthentication` is enabled.
+    pub fn cookie_path(&self) -> Option<PathBuf> {
+        if !self.cookie_authentication {
+            return None;
+        }
+        self.cookie_auth_file.clone().or_else(|| {
+            self.data_directory
+                .as_ref()
+                .map(|data_directory| data_directory.join("control_auth_cookie"))
+        })
+    }
+}
+
+/// Applies a single option, keywords are case insensitive as in Tor.
+fn apply(config: &mut Config, keyword: &str, value: &str) -> Result<(), String> {
+    match keyword.to_lowercase().as_str() {
+        "datadirectory" => config.data_directory = Some(PathBuf::from(required(keyword, value)?)),
+        "socksport" => config.socks_port = Some(port(keyword, value)?),
+        "controlport" => match value.strip_prefix("unix:") {
+            Some(path) => config.control_socket = Some(PathBuf::from(required(keyword, path)?)),
+            None => config.control_port = Some(port(keyword, value)?),
+        },
+        "controlportwritetofile" => {
+            config.control_port_write_to_file = Some(PathBuf::from(required(keyword, value)?))
+        }
+        "controlsocket" => config.control_socket = Some(PathBuf::from(required(keyword, value)?)),
+        "cookieauthentication" => config.cookie_authentication = boolean(keyword, value)?,
+        "cookieauthfile" => {
+            config.cookie_auth_file = Some(PathBuf::from(required(keyword, value)?))
+        }
+        "hashedcontrolpassword" => {
+            config.hashed_control_password = Some(required(keyword, value)?.to_string())
+        }
+        "hiddenservicedir" => config.hidden_services.push(HiddenService {
+            directory: PathBuf::from(required(keyword, value)?),
+            ports: Vec::new(),
+        }),
+        "hiddenserviceport" => {
+            let hidden_service = config.hidden_services.last_mut().ok_or_else(|| {
+                "HiddenServicePort with no preceding HiddenServiceDir directive".to_string()
+            })?;
+            let virtual_port = value.split_whitespace().next().unwrap_or_default();
+            if virtual_port.parse::<u16>().map_or(true, |port| port == 0) {
+                return Err(format!(
+                    "Missing or invalid port {} in hidden service port configuration.",
+                    value
+                ));
+            }
+            hidden_service.ports.push(value.to_string());
+        }
+        "log" => config.logger = logger(value)?,
+        _ if IGNORED_OPTIONS
+            .iter()
+            .any(|option| option.eq_ignore_ascii_case(keyword)) => {}
+        _ => return Err(format!("Unknown option '{}'.  Failing.", keyword)),
+    }
+
+    Ok(())
+}
+
+fn required<'a>(keyword: &str, value: &'a str) -> Result<&'a str, String> {
+    match value {
+        "" => Err(format!("Option '{}' requires a value.", keyword)),
+        value => Ok(value),
+    }
+}
+
+/// Parses a port, which may be `auto` or prefixed with an address.
+fn port(keyword: &str, value: &str) -> Result<String, String> {
+    let port = value
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .rsplit(':')
+        .next()
+        .unwrap_or_default();
+
+    if port == "auto" || port.parse::<u16>().is_ok() {
+        Ok(value.to_string())
+    } else {
+        Err(format!("Invalid {} '{}'.", keyword, value))
+    }
+}
+
+fn boolean(keyword: &str, value: &str) -> Result<bool, String> {
+    match value {
+        "0" => Ok(false),
+        "1" => Ok(true),
+        _ => Err(format!(
+            "Could not parse {} '{}': Expected 0 or 1.",
+            keyword, value
+        )),
+    }
+}
+
+/// Parses `Log <severity> stdout|stderr`.
+fn logger(value: &str) -> Result<Logger, String> {
+    let mut parts = value.split_whitespace();
+    let severity = parts
+        .next()
+        .and_then(|severity| Severity::parse(severity.split('-').next().unwrap_or_default()))
+        .ok_or_else(|| format!("Couldn't parse log levels in Log option 'Log {}'", value))?;
+
+    let stderr = match parts.next() {
+        Some("stdout") | None => false,
+        Some("stderr") => true,
+        Some(other) => return Err(format!("Unsupported log destination '{}'.", other)),
+    };
+
+    Ok(Logger {
+        minimum: severity,
+        stderr,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_hidden_services() {
+        let config = parse(
+            "# comment\n\
+             SocksPort 9050\n\
+             HiddenServiceDir hs/first\n\
+             HiddenServicePort 80 127.0.0.1:8080\n\
+             HiddenServiceDir hs/second\n\
+             HiddenServicePort 443 127.0.0.1:8443 # trailing comment\n",
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(Some("9050".to_string()), config.socks_port);
+        assert_eq!(
+            vec![
+                HiddenService {
+                    directory: PathBuf::from("hs/first"),
+                    ports: vec!["80 127.0.0.1:8080".to_string()]
+                },
+                HiddenService {
+                    directory: PathBuf::from("hs/second"),
+                    ports: vec!["443 127.0.0.1:8443".to_string()]
+                }
+            ],
+            config.hidden_services
+        );
+    }
+
+    #[test]
+    fn parse_applies_command_line_options_last() {
+        let config = parse(
+            "DataDirectory torrc-data\n",
+            &[
+                ("DataDirectory".to_string(), "data".to_string()),
+                ("Log".to_string(), "warn stderr".to_string()),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(Some(PathBuf::from("data")), config.data_directory);
+        assert_eq!(
+            Logger {
+                minimum: Severity::Warn,
+                stderr: true
+            },
+            config.logger
+        );
+    }
+
+    #[test]
+    fn parse_control_options() {
+        let config = parse(
+            "ControlPort unix:/run/tor/control\n\
+             ControlPort 127.0.0.1:auto\n\
+             CookieAuthentication 1\n\
+             DataDirectory data\n",
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(
+            Some(PathBuf::from("/run/tor/control")),
+            config.control_socket
+        );
+        assert_eq!(Some("127.0.0.1:auto".to_string()), config.control_port);
+        assert_eq!(
+            Some(PathBuf::from("data").join("control_auth_cookie")),
+            config.cookie_path()
+        );
+    }
+
+    #[test]
+    fn parse_rejects_invalid_configs() {
+        assert!(parse("Invalid 1\n", &[]).is_err());
+        assert!(parse("HiddenServicePort 80\n", &[]).is_err());
+        assert!(parse("HiddenServiceDir hs\n", &[]).is_err());
+        assert!(parse("HiddenServiceDir hs\nHiddenServicePort http\n", &[]).is_err());
+        assert!(parse("SocksPort socks\n", &[]).is_err());
+        assert!(parse("CookieAuthentication yes\n", &[]).is_err());
+        assert!(parse("CookieAuthentication 1\n", &[]).is_err());
+    }
+}
@@ -1,7 +1,32 @@
+use crate::error::Error;
+use crate::privileges::Privileges;
+use crate::tor_log::Severity;
+use crate::tor_options::{ControlPort, TorOptions};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+/// Determines where the output of Tor goes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StdioPolicy {
+    /// Forwards output into `tracing` events under the `tor` target.
+    Forward,
+    /// Inherits the output streams of the current process.
+    Inherit,
+    /// Discards output.
+    Null,
+}
+
 #[derive(Clone)]
 pub struct Command {
     program: String,
     no_window_support: bool,
+    options: TorOptions,
+    args: Vec<String>,
+    envs: Vec<(String, String)>,
+    current_dir: Option<PathBuf>,
+    stdio: StdioPolicy,
+    privileges: Privileges,
+    output_lines: usize,
 }
 
 impl Command {
@@ -20,18 +45,165 @@ impl Command {
         Self {
             program: program.to_string(),
             no_window_support,
+            options: TorOptions::default(),
+            args: Vec::new(),
+            envs: Vec::new(),
+            current_dir: None,
+            stdio: StdioPolicy::Forward,
+            privileges: Privileges::default(),
+            output_lines: 100,
         }
     }
 
+    /// Sets the torrc Tor reads its configuration from, `-f`.
+    pub fn torrc(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.options.torrc = Some(path.into());
+        self
+    }
+
+    /// Sets the `DataDirectory` of Tor.
+    pub fn data_directory(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.options.data_directory = Some(path.into());
+        self
+    }
+
+    /// Sets the `ControlPort` or `ControlSocket` of Tor.
+    pub fn control_port(&mut self, control_port: ControlPort) -> &mut Self {
+        self.options.control_port = Some(control_port);
+        self
+    }
+
+    /// Sets whether Tor requires cookie authentication on the control port.
+    pub fn cookie_authentication(&mut self, enabled: bool) -> &mut Self {
+        self.options.cookie_authentication = Some(enabled);
+        self
+    }
+
+    /// Sets the `SocksPort` of Tor.
+    pub fn socks_port(&mut self, port: u16) -> &mut Self {
+        self.options.socks_port = Some(port);
+        self
+    }
+
+    /// Sets the minimum severity Tor logs to stdout.
+    pub fn log_level(&mut self, severity: Severity) -> &mut Self {
+        self.options.log_level = Some(severity);
+        self
+    }
+
+    /// Adds an argument to pass to Tor.
+    pub fn arg(&mut self, arg: &str) -> &mut Self {
+        self.args.push(arg.to_string());
+        self
+    }
+
+    /// Sets an environment variable for Tor.
+    pub fn env(&mut self, key: &str, value: &str) -> &mut Self {
+        self.envs.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Sets the working directory of Tor.
+    pub fn current_dir(&mut self, dir: impl Into<PathBuf>) -> &mut Self {
+        self.current_dir = Some(dir.into());
+        self
+    }
+
+    /// Sets where the output of Tor goes.
+    pub fn stdio(&mut self, stdio: StdioPolicy) -> &mut Self {
+        self.stdio = stdio;
+        self
+    }
+
+    /// Sets how many of the most recent lines of forwarded output are kept per run, defaults
+    /// to 100.
+    pub fn output_lines(&mut self, lines: usize) -> &mut Self {
+        self.output_lines = lines;
+        self
+    }
+
+    /// Runs Tor as the user and group ids instead of those of the current process.
+    pub fn user(&mut self, uid: u32, gid: u32) -> &mut Self {
+        self.privileges.user = Some((uid, gid));
+        self
+    }
+
+    /// Limits the number of file descriptors Tor can open.
+    pub fn open_files_limit(&mut self, limit: u64) -> &mut Self {
+        self.privileges.open_files = Some(limit);
+        self
+    }
+
+    /// Limits the virtual memory of Tor in bytes.
+    pub fn memory_limit(&mut self, bytes: u64) -> &mut Self {
+        self.privileges.memory = Some(bytes);
+        self
+    }
+
+    /// Sets the file mode creation mask of Tor, e.g. `0o077`.
+    pub fn umask(&mut self, umask: u32) -> &mut Self {
+        self.privileges.umask = Some(umask);
+        self
+    }
+
+    /// Clears the environment inherited from the current process, keeping only variables set
+    /// with `env`.
+    pub fn env_clear(&mut self) -> &mut Self {
+        self.privileges.clear_env = true;
+        self
+    }
+
+    /// Checks the command can be spawned with its privileges, explaining why if it cannot.
+    pub fn validate(&self) -> Result<(), Error> {
+        self.privileges.validate(&self.program)
+    }
+
+    /// Path of the Tor executable.
+    pub fn program(&self) -> &str {
+        &self.program
+    }
+
+    /// Typed Tor options.
+    pub fn options(&self) -> &TorOptions {
+        &self.options
+    }
+
+    /// Arguments passed to Tor, typed options followed by any additional arguments.
+    pub fn args(&self) -> Vec<String> {
+        let mut args = self.options.render();
+        args.extend(self.args.iter().cloned());
+        args
+    }
+
+    /// How many of the most recent lines of forwarded output are kept per run.
+    pub fn output_capacity(&self) -> usize {
+        self.output_lines
+    }
+
+    /// Where the output of Tor goes.
+    pub fn stdio_policy(&self) -> StdioPolicy {
+        self.stdio
+    }
+
     #[cfg(target_family = "unix")]
     pub fn create(&self) -> tokio::process::Command {
         /* Runs program in a new session to avoid spawned child process to receive double SIGINT
          *   * one SIGINT from current program during graceful shutdown
          *   * one SIGINT from terminal when CTRL+C sends SIGINT to all process in same PGID
+         *
+         * The session is created in the forked child before exec so the PID of the child is the
+         * PID of Tor, which also leads the new process group.
          */
 
-        let mut command = tokio::process::Command::new("setsid");
-        command.arg(&self.program);
+        let mut command = tokio::process::Command::new(&self.program);
+        command.args(self.args());
+        self.configure(&mut command);
+        unsafe {
+            command.pre_exec(|| match libc::setsid() {
+                -1 => Err(std::io::Error::last_os_error()),
+                _ => Ok(()),
+            });
+        }
         command
     }
 
@@ -42,10 +214,164 @@ impl Command {
              * Runs program using powershell in order for program to receive signals.
              */
             let mut command = tokio::process::Command::new("powershell");
-            command.arg(format!("{} | more", &self.program));
+            command.arg(format!(
+                "{} {} | more",
+                &self.program,
+                self.args().join(" ")
+            ));
+            self.configure(&mut command);
             command
         } else {
-            tokio::process::Command::new(&self.program)
+            let mut command = tokio::process::Command::new(&self.program);
+            command.args(self.args());
+            self.configure(&mut command);
+            command
+        }
+    }
+
+    /// Creates a command which checks the torrc at `torrc` with `--verify-config` instead of
+    /// running Tor.
+    pub fn verify_config(&self, torrc: &Path) -> tokio::process::Command {
+        let mut options = self.options.clone();
+        options.torrc = Some(torrc.to_path_buf());
+
+        let mut command = tokio::process::Command::new(&self.program);
+        command
+            .arg("--verify-config")
+            .args(options.render())
+            .args(&self.args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        self.environment(&mut command);
+        command
+    }
+
+    /// Applies the privileges, environment, working directory and stdio policy.
+    fn configure(&self, command: &mut tokio::process::Command) {
+        self.environment(command);
+
+        match self.stdio {
+            StdioPolicy::Forward => {}
+            StdioPolicy::Inherit => {
+                command.stdout(Stdio::inherit()).stderr(Stdio::inherit());
+            }
+            StdioPolicy::Null => {
+                command.stdout(Stdio::null()).stderr(Stdio::null());
+            }
+        }
+    }
+
+    /// Applies the privileges, environment and working directory.
+    fn environment(&self, command: &mut tokio::process::Command) {
+        self.privileges.apply(command);
+
+        command.envs(self.envs.iter().map(|(key, value)| (key, value)));
+
+        if let Some(dir) = &self.current_dir {
+            command.current_dir(dir);
         }
     }
 }
+
+#[cfg(target_family = "unix")]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn create_passes_arguments_environment_and_directory() {
+        // Arrange
+        let mut command = Command::new("sh", false);
+        command
+            .arg("-c")
+            .arg("echo $TOR_TEST $(pwd)")
+            .env("TOR_TEST", "value")
+            .current_dir("/");
+
+        // Act
+        let output = command
+            .create()
+            .stdout(Stdio::piped())
+            .output()
+            .await
+            .expect("Failed to run command.");
+
+        // Assert
+        assert_eq!("value /\n", String::from_utf8_lossy(&output.stdout));
+    }
+
+    #[test]
+    fn args_render_typed_options_before_additional_arguments() {
+        // Arrange
+        let mut command = Command::new("tor", false);
+        command
+            .arg("--RunAsDaemon")
+            .arg("0")
+            .torrc("torrc")
+            .control_port(ControlPort::Port(9051))
+            .log_level(Severity::Warn);
+
+        // Act
+        let args = command.args();
+
+        // Assert
+        assert_eq!(
+            vec![
+                "-f",
+                "torrc",
+                "--ControlPort",
+                "9051",
+                "--Log",
+                "warn stdout",
+                "--RunAsDaemon",
+                "0"
+            ],
+            args
+        );
+    }
+
+    #[tokio::test]
+    async fn create_passes_typed_options() {
+        // Arrange
+        let mut command = Command::new("echo", false);
+        command.socks_port(9050).cookie_authentication(false);
+
+        // Act
+        let output = command
+            .create()
+            .stdout(Stdio::piped())
+            .output()
+            .await
+            .expect("Failed to run command.");
+
+        // Assert
+        assert_eq!(
+            "--CookieAuthentication 0 --SocksPort 9050\n",
+            String::from_utf8_lossy(&output.stdout)
+        );
+    }
+
+    #[tokio::test]
+    async fn create_runs_program_as_session_leader() {
+        // Arrange
+        let command = Command::new("sh", false);
+        let mut command = command.create();
+        command
+            .arg("-c")
+            .arg("echo $$ $(ps -o sid= -p $$)")
+            .stdout(Stdio::piped());
+
+        // Act
+        let child = command.spawn().expect("Failed to spawn command.");
+        let id = child.id().expect("Failed to get PID.");
+        let output = child
+            .wait_with_output()
+            .await
+            .expect("Failed to run command.");
+
+        // Assert
+        let output = String::from_utf8_lossy(&output.stdout);
+        let ids: Vec<&str> = output.split_whitespace().collect();
+        assert_eq!(vec![id.to_string(), id.to_string()], ids);
+    }
+}
@@ -1,5 +1,15 @@
 use crate::command::Command;
-use crate::scheduler::Scheduler;
+use crate::controller_builder::ControllerBuilder;
+use crate::error::Error;
+use crate::lifecycle::Event;
+use crate::orphan::OrphanPolicy;
+use crate::restart_policy::RestartPolicy;
+use crate::scheduler::{PendingReload, Scheduler};
+use crate::status::{State, Status};
+use crate::stop_policy::{StopPolicy, Termination};
+use std::path::Path;
+use std::time::Duration;
+use tokio::sync::{broadcast, watch};
 
 /// Interface with server
 pub struct Controller {
@@ -7,25 +17,88 @@ pub struct Controller {
 }
 
 impl Controller {
-    pub fn new(command: Command) -> Self {
-        Self {
-            scheduler: Scheduler::new(command, "tor.pid"),
-        }
+    pub fn new(
+        command: Command,
+        restart_policy: RestartPolicy,
+        stop_policy: StopPolicy,
+        orphan_policy: OrphanPolicy,
+    ) -> Self {
+        Self::builder(command)
+            .restart_policy(restart_policy)
+            .stop_policy(stop_policy)
+            .orphan_policy(orphan_policy)
+            .build()
     }
 
-    pub fn start(&mut self) {
-        let _ = self.scheduler.start();
+    /// Returns a builder to configure the PID file and how Tor is spawned.
+    pub fn builder(command: Command) -> ControllerBuilder {
+        ControllerBuilder::new(command)
     }
 
-    pub async fn stop(&mut self) {
-        let _ = self.scheduler.stop().await;
+    pub(crate) fn from_scheduler(scheduler: Scheduler) -> Self {
+        Self { scheduler }
     }
 
-    pub fn create_hidden_service(&mut self) {
-        self.scheduler.reload();
+    pub async fn start(&mut self) -> Result<(), Error> {
+        self.scheduler.start().await
     }
 
-    pub fn delete_hidden_service(&mut self) {
-        self.scheduler.reload();
+    /// Stops Tor, returning the signal that stopped it and its final exit status.
+    pub async fn stop(&mut self) -> Result<Option<Termination>, Error> {
+        self.scheduler.stop().await
+    }
+
+    /// Reloads Tor, returning the generation of the reload which included the change.
+    pub async fn create_hidden_service(&mut self) -> Result<u64, Error> {
+        self.scheduler.reload().await
+    }
+
+    /// Reloads Tor, returning the generation of the reload which included the change.
+    pub async fn delete_hidden_service(&mut self) -> Result<u64, Error> {
+        self.scheduler.reload().await
+    }
+
+    /// Verifies the `candidate` torrc with `tor --verify-config`, swapping it in and reloading Tor
+    /// only if it is accepted. Otherwise the last known good torrc is kept and Tor's diagnostic
+    /// output is returned. Returns the generation of the reload which included the candidate, or
+    /// a later candidate which replaced it before the reload.
+    pub async fn reload_config(&mut self, candidate: &Path) -> Result<u64, Error> {
+        self.scheduler.reload_config(candidate).await
+    }
+
+    /// Queues a reload of the `candidate` torrc, as `reload_config` does, returning once it is
+    /// queued rather than reloaded.
+    pub async fn queue_reload_config(&self, candidate: &Path) -> Result<PendingReload, Error> {
+        self.scheduler.queue_reload_config(candidate).await
+    }
+
+    /// Restarts Tor using `command`.
+    pub async fn reconfigure(&mut self, command: Command) -> Result<(), Error> {
+        self.scheduler.reconfigure(command).await
+    }
+
+    /// Changes the window over which reloads are coalesced.
+    pub async fn set_reload_debounce(&mut self, reload_debounce: Duration) -> Result<(), Error> {
+        self.scheduler.set_reload_debounce(reload_debounce).await
+    }
+
+    /// Returns a snapshot of the Tor process status.
+    pub fn status(&self) -> Status {
+        self.scheduler.status()
+    }
+
+    /// Returns the lifecycle state of the Tor process.
+    pub fn state(&self) -> State {
+        self.scheduler.state()
+    }
+
+    /// Returns a receiver notified of every change to the Tor process status.
+    pub fn watch(&self) -> watch::Receiver<Status> {
+        self.scheduler.watch()
+    }
+
+    /// Returns a receiver of the Tor process lifecycle events published from now on.
+    pub fn events(&self) -> broadcast::Receiver<Event> {
+        self.scheduler.events()
     }
 }
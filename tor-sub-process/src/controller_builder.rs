@@ -0,0 +1,150 @@
+use crate::command::{Command, StdioPolicy};
+use crate::controller::Controller;
+use crate::orphan::OrphanPolicy;
+use crate::restart_policy::RestartPolicy;
+use crate::scheduler::Scheduler;
+use crate::stop_policy::StopPolicy;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Builds a controller, configuring where its PID file lives and how Tor is spawned.
+pub struct ControllerBuilder {
+    command: Command,
+    pid: String,
+    restart_policy: RestartPolicy,
+    stop_policy: StopPolicy,
+    orphan_policy: OrphanPolicy,
+    reload_debounce: Duration,
+}
+
+impl ControllerBuilder {
+    pub fn new(command: Command) -> Self {
+        Self {
+            command,
+            pid: "tor.pid".to_string(),
+            restart_policy: RestartPolicy::default(),
+            stop_policy: StopPolicy::default(),
+            orphan_policy: OrphanPolicy::default(),
+            reload_debounce: Duration::from_millis(250),
+        }
+    }
+
+    /// Sets the path of the PID file, defaults to `tor.pid` in the current directory.
+    pub fn pid(mut self, path: &str) -> Self {
+        self.pid = path.to_string();
+        self
+    }
+
+    /// Sets the working directory of Tor.
+    pub fn current_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.command.current_dir(dir);
+        self
+    }
+
+    /// Sets an environment variable for Tor.
+    pub fn env(mut self, key: &str, value: &str) -> Self {
+        self.command.env(key, value);
+        self
+    }
+
+    /// Adds an argument to pass to Tor, e.g. `-f`.
+    pub fn arg(mut self, arg: &str) -> Self {
+        self.command.arg(arg);
+        self
+    }
+
+    /// Sets where the output of Tor goes.
+    pub fn stdio(mut self, stdio: StdioPolicy) -> Self {
+        self.command.stdio(stdio);
+        self
+    }
+
+    pub fn restart_policy(mut self, restart_policy: RestartPolicy) -> Self {
+        self.restart_policy = restart_policy;
+        self
+    }
+
+    pub fn stop_policy(mut self, stop_policy: StopPolicy) -> Self {
+        self.stop_policy = stop_policy;
+        self
+    }
+
+    pub fn orphan_policy(mut self, orphan_policy: OrphanPolicy) -> Self {
+        self.orphan_policy = orphan_policy;
+        self
+    }
+
+    /// Sets the window over which reloads are coalesced, defaults to 250ms.
+    pub fn reload_debounce(mut self, reload_debounce: Duration) -> Self {
+        self.reload_debounce = reload_debounce;
+        self
+    }
+
+    pub fn build(self) -> Controller {
+        Controller::from_scheduler(Scheduler::new(
+            self.command,
+            &self.pid,
+            self.restart_policy,
+            self.stop_policy,
+            self.orphan_policy,
+            self.reload_debounce,
+        ))
+    }
+}
+
+/// Controllers stop Tor with SIGTERM, which does not exist on Windows.
+#[cfg(target_family = "unix")]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::status::State;
+    use fake::{Fake, Faker};
+
+    #[tokio::test]
+    async fn controllers_with_distinct_pid_paths_do_not_collide() {
+        // Arrange
+        let first = format!("test-{}.pid", Faker.fake::<String>());
+        let second = format!("test-{}.pid", Faker.fake::<String>());
+        let mut controllers = [
+            create_builder().pid(&first).build(),
+            create_builder().pid(&second).build(),
+        ];
+
+        // Act
+        for controller in controllers.iter_mut() {
+            controller.start().await.expect("Failed to start.");
+        }
+        let mut states = Vec::new();
+        for controller in controllers.iter() {
+            let mut status = controller.watch();
+            while status.borrow().state != State::Running {
+                status.changed().await.expect("Failed to watch status.");
+            }
+            states.push(controller.state());
+        }
+        for controller in controllers.iter_mut() {
+            controller.stop().await.expect("Failed to stop.");
+        }
+
+        // Assert
+        assert_eq!(vec![State::Running, State::Running], states);
+        assert!(!std::path::Path::new(&first).exists());
+        assert!(!std::path::Path::new(&second).exists());
+        for path in [first, second].iter() {
+            std::fs::remove_file(format!("{}.lock", path)).expect("Failed to delete test file.");
+        }
+    }
+
+    fn create_builder() -> ControllerBuilder {
+        let path = std::env::current_dir()
+            .unwrap()
+            .join("../target/debug/tor-stub");
+
+        if !std::path::Path::new(&path).exists() {
+            panic!("tor-stub does not exist. Please run cargo build --workspace then try again.");
+        }
+        Controller::builder(Command::new(path.to_str().unwrap(), false))
+            .stdio(StdioPolicy::Null)
+            .current_dir(std::env::current_dir().unwrap())
+    }
+}
@@ -0,0 +1,70 @@
+/// Errors raised while supervising Tor.
+#[derive(Debug)]
+pub enum Error {
+    /// The job is already running.
+    AlreadyRunning,
+    /// The job is not running.
+    NotRunning,
+    /// The job could not be spawned.
+    Spawn(std::io::Error),
+    /// The job could not be signalled.
+    Signal(std::io::Error),
+    /// The PID file could not be read or written.
+    Pid(std::io::Error),
+    /// The command cannot be spawned as configured.
+    InvalidCommand(String),
+    /// Tor rejected the configuration, with its diagnostic output.
+    InvalidConfig(String),
+    /// The job exited while a reload of it was pending.
+    ExitedBeforeReload(std::process::ExitStatus),
+    /// The reload generation which included the request failed.
+    Reload {
+        generation: u64,
+        error: std::sync::Arc<Error>,
+    },
+    /// Any other IO failure.
+    Io(std::io::Error),
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::AlreadyRunning
+            | Error::NotRunning
+            | Error::ExitedBeforeReload(_)
+            | Error::InvalidCommand(_)
+            | Error::InvalidConfig(_) => None,
+            Error::Reload { error, .. } => Some(error.as_ref()),
+            Error::Spawn(error) | Error::Signal(error) | Error::Pid(error) | Error::Io(error) => {
+                Some(error)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::AlreadyRunning => write!(f, "Job is already running."),
+            Error::NotRunning => write!(f, "Job is not running."),
+            Error::Spawn(error) => write!(f, "Failed to spawn job: {}", error),
+            Error::Signal(error) => write!(f, "Failed to signal job: {}", error),
+            Error::Pid(error) => write!(f, "Failed to access PID file: {}", error),
+            Error::InvalidCommand(reason) => write!(f, "Invalid command: {}", reason),
+            Error::InvalidConfig(output) => write!(f, "Tor rejected the configuration: {}", output),
+            Error::ExitedBeforeReload(status) => {
+                write!(f, "Job exited with {} before reloading.", status)
+            }
+            Error::Reload { generation, error } => {
+                write!(f, "Reload generation {} failed: {}", generation, error)
+            }
+            Error::Io(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Io(error)
+    }
+}
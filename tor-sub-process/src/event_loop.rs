@@ -1,155 +1,761 @@
-use crate::command::Command;
+use crate::command::{Command, StdioPolicy};
+use crate::error::Error;
 use crate::job::Job;
+use crate::lifecycle::{Event, Lifecycle};
 use crate::pid::Pid;
-use std::sync::atomic::{AtomicBool, Ordering};
+use crate::restart_policy::{Restart, RestartHistory, RestartPolicy};
+use crate::status::State;
+use crate::stop_policy::{StopPolicy, Termination};
+use crate::torrc;
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
 use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::Instant;
 
-/// Maintains a jobs lifecycle until signaled to terminate.
+/// Acknowledges a request with its outcome.
+pub type Ack = oneshot::Sender<Result<(), Error>>;
+
+/// Acknowledges a reload request with the generation of the reload which included it.
+pub type ReloadAck = oneshot::Sender<Result<u64, Error>>;
+
+/// Requests sent to the event loop.
+pub enum Request {
+    /// Verifies the candidate torrc, or the current torrc if none, then reloads the job.
+    Reload(Option<PathBuf>, ReloadAck),
+    /// Replaces the command and restarts the job with it.
+    Reconfigure(Box<Command>, Ack),
+    /// Changes the window over which reloads requested from now on are coalesced.
+    Debounce(Duration, Ack),
+    /// Stops the job and exits the event loop, acknowledged by the event loop completing.
+    Stop,
+}
+
+/// Maintains a jobs lifecycle until requested to stop.
 pub async fn event_loop(
-    command: Command,
+    mut command: Command,
     pid: Pid,
-    reload: Arc<AtomicBool>,
-    terminate: Arc<AtomicBool>,
-) {
-    let (mut job, id) = start_job(&command);
-    save_pid(&pid, id);
+    restart_policy: RestartPolicy,
+    stop_policy: StopPolicy,
+    mut reload_debounce: Duration,
+    mut lifecycle: Lifecycle,
+    mut requests: mpsc::Receiver<Request>,
+) -> Result<Option<Termination>, Error> {
+    let mut history = RestartHistory::default();
+    let mut restart_at = None;
+    let mut reload_at = None;
+    let mut reloads = Reloads::default();
+    let mut job = None;
+
+    let result = async {
+        job = Some(start_job(&command, &pid, &mut lifecycle)?);
+
+        loop {
+            tokio::select! {
+                exit_status = job_exited(&mut job), if job.is_some() => {
+                    let output = match job.take() {
+                        Some(mut exited) => exited.recent_output().await,
+                        None => Vec::new(),
+                    };
+                    let exit_status = exit_status?;
+                    if !exit_status.success() {
+                        tracing::warn!(status = ?exit_status, output = %output.join("\n"), "job exited unexpectedly");
+                    }
+                    lifecycle.emit(Event::Exited { status: exit_status, output });
+                    // reloads queued for the exited job fail rather than reload its replacement
+                    if reload_at.take().is_some() {
+                        let (generation, _, acks) = reloads.take();
+                        reloads.complete(generation, acks, Err(Error::ExitedBeforeReload(exit_status)));
+                    }
+                    restart_at = schedule_restart(&restart_policy, &mut history, &mut lifecycle, exit_status.success());
+                    if restart_at.is_none() {
+                        break;
+                    }
+                }
+                _ = due(restart_at), if restart_at.is_some() => {
+                    restart_at = None;
+                    match start_job(&command, &pid, &mut lifecycle) {
+                        Ok(new_job) => job = Some(new_job),
+                        Err(error) => {
+                            tracing::error!(%error, "failed to restart job");
+                            restart_at = schedule_restart(&restart_policy, &mut history, &mut lifecycle, false);
+                            if restart_at.is_none() {
+                                break;
+                            }
+                        }
+                    }
+                }
+                _ = due(reload_at), if reload_at.is_some() => {
+                    reload_at = None;
+                    let (generation, candidate, acks) = reloads.take();
+                    let reloaded = reload_config(&mut job, &command, candidate.as_deref(), &pid, &stop_policy, &mut lifecycle).await;
+                    reloads.complete(generation, acks, reloaded);
+                }
+                request = requests.recv() => match request {
+                    Some(Request::Reload(candidate, ack)) => {
+                        reloads.push(candidate, ack);
+                        if reload_at.is_none() {
+                            reload_at = Some(Instant::now() + reload_debounce);
+                        }
+                    }
+                    Some(Request::Reconfigure(new_command, ack)) => {
+                        command = *new_command;
+                        let _ = ack.send(restart_job(&mut job, &command, &pid, &stop_policy, &mut lifecycle).await);
+                        if job.is_none() && restart_at.is_none() {
+                            restart_at = schedule_restart(&restart_policy, &mut history, &mut lifecycle, false);
+                            if restart_at.is_none() {
+                                break;
+                            }
+                        }
+                    }
+                    Some(Request::Debounce(debounce, ack)) => {
+                        reload_debounce = debounce;
+                        let _ = ack.send(Ok(()));
+                    }
+                    Some(Request::Stop) | None => break,
+                },
+            }
+        }
+
+        Ok(())
+    }
+    .await;
+
+    reloads.abandon();
+
+    let stopped = match job.take() {
+        Some(mut job) => match stop_job(&mut job, &stop_policy).await {
+            Ok(termination) => {
+                lifecycle.emit(Event::Exited {
+                    status: termination.output.status,
+                    output: job.recent_output().await,
+                });
+                Ok(Some(termination))
+            }
+            Err(error) => Err(error),
+        },
+        None => Ok(None),
+    };
+
+    if result.is_err() {
+        lifecycle.emit(Event::Failed);
+    } else if lifecycle.status().state != State::Failed {
+        lifecycle.emit(Event::Stopped);
+    }
+
+    let deleted = delete_pid(&pid);
+
+    result.and(deleted).and(stopped)
+}
 
-    while !termination_requested(&terminate) {
-        if job_died(&mut job).await {
-            stop_job(&mut job).await;
-            let (new_job, id) = start_job(&command);
-            save_pid(&pid, id);
-            job = new_job;
+/// Decides whether to restart a job which exited, returning when the restart is due.
+fn schedule_restart(
+    restart_policy: &RestartPolicy,
+    history: &mut RestartHistory,
+    lifecycle: &mut Lifecycle,
+    success: bool,
+) -> Option<Instant> {
+    match restart_policy.decide(history, success, Instant::now().into_std()) {
+        Restart::After(delay) => {
+            tracing::warn!(?delay, "job exited, restarting");
+            let attempt = lifecycle.status().restarts + 1;
+            lifecycle.emit(Event::Restarting { attempt });
+            Some(Instant::now() + delay)
+        }
+        Restart::Stop => {
+            tracing::info!("job exited");
+            None
+        }
+        Restart::Fail => {
+            tracing::error!("job failed");
+            lifecycle.emit(Event::Failed);
+            None
         }
+    }
+}
+
+/// Completes once `at` is due.
+async fn due(at: Option<Instant>) {
+    if let Some(at) = at {
+        tokio::time::sleep_until(at).await;
+    }
+}
+
+/// Reload requests coalesced into the next reload generation.
+///
+/// Candidate torrcs are whole files so they cannot be merged, a later candidate supersedes an
+/// earlier one. Requests for the earlier candidate stay pending and are acknowledged with the
+/// outcome of the generation which reloads the later one, as are requests without a candidate.
+#[derive(Default)]
+struct Reloads {
+    generation: u64,
+    candidate: Option<PathBuf>,
+    acks: Vec<ReloadAck>,
+}
 
-        if reload_requested(&reload) {
-            let (new_job, id) = reload_job(job, &command).await;
-            save_pid(&pid, id);
-            job = new_job;
+impl Reloads {
+    /// Adds a request to the next generation.
+    fn push(&mut self, candidate: Option<PathBuf>, ack: ReloadAck) {
+        if candidate.is_some() {
+            self.candidate = candidate;
         }
+        self.acks.push(ack);
+    }
+
+    /// Takes the requests of the next generation.
+    fn take(&mut self) -> (u64, Option<PathBuf>, Vec<ReloadAck>) {
+        self.generation += 1;
+        (
+            self.generation,
+            self.candidate.take(),
+            std::mem::take(&mut self.acks),
+        )
+    }
 
-        sleep().await;
+    /// Acknowledges every request included in `generation` with its outcome.
+    fn complete(&self, generation: u64, acks: Vec<ReloadAck>, result: Result<(), Error>) {
+        match result {
+            Ok(()) => {
+                tracing::info!(generation, requests = acks.len(), "job reloaded");
+                for ack in acks {
+                    let _ = ack.send(Ok(generation));
+                }
+            }
+            Err(error) => {
+                tracing::error!(generation, %error, "failed to reload job");
+                let error = Arc::new(error);
+                for ack in acks {
+                    let _ = ack.send(Err(Error::Reload {
+                        generation,
+                        error: error.clone(),
+                    }));
+                }
+            }
+        }
     }
 
-    stop_job(&mut job).await;
-    delete_pid(&pid);
+    /// Rejects requests which will never be included in a generation.
+    fn abandon(&mut self) {
+        for ack in self.acks.drain(..) {
+            let _ = ack.send(Err(Error::NotRunning));
+        }
+    }
 }
 
 /// Deletes a pid.
-fn delete_pid(pid: &Pid) {
-    pid.reset().expect("Failed to delete PID.");
+fn delete_pid(pid: &Pid) -> Result<(), Error> {
+    pid.reset().map_err(Error::Pid)
 }
 
 /// Saves a pid.
-fn save_pid(pid: &Pid, id: u32) {
-    pid.update(id).expect("Failed to save PID.");
+fn save_pid(pid: &Pid, id: u32) -> Result<(), Error> {
+    pid.update(id).map_err(Error::Pid)
 }
 
-/// Creates and starts a job.
-fn start_job(command: &Command) -> (Job, u32) {
-    let mut job = Job::new(command.create());
-    job.start().expect("Failed to start job.");
-    let id = job.id().expect("Failed to get PID.");
-    (job, id)
+/// Creates and starts a job, saving its pid.
+fn start_job(command: &Command, pid: &Pid, lifecycle: &mut Lifecycle) -> Result<Job, Error> {
+    lifecycle.emit(Event::Starting);
+
+    command.validate()?;
+    let mut child = command.create();
+    child.kill_on_drop(true);
+    let mut job = Job::new(child);
+    if command.stdio_policy() == StdioPolicy::Forward {
+        job = job.forward_output(command.output_capacity());
+    }
+    job.start()?;
+    let id = job.id().ok_or(Error::NotRunning)?;
+    save_pid(pid, id)?;
+
+    lifecycle.emit(Event::Running { pid: id });
+    Ok(job)
 }
 
 /// Stops a job.
-async fn stop_job(job: &mut Job) {
-    job.stop().await.expect("Failed to stop job.");
+async fn stop_job(job: &mut Job, stop_policy: &StopPolicy) -> Result<Termination, Error> {
+    let termination = job.stop(stop_policy).await?;
+    tracing::info!(
+        escalation = ?termination.escalation,
+        status = ?termination.output.status,
+        "job stopped"
+    );
+    Ok(termination)
 }
 
-/// Reloads a job.
-#[cfg(target_family = "unix")]
-async fn reload_job(job: Job, _command: &Command) -> (Job, u32) {
-    job.reload();
-    let id = job.id().expect("Failed to get PID.");
-    (job, id)
+/// Stops the running job then starts it again.
+async fn restart_job(
+    job: &mut Option<Job>,
+    command: &Command,
+    pid: &Pid,
+    stop_policy: &StopPolicy,
+    lifecycle: &mut Lifecycle,
+) -> Result<(), Error> {
+    if let Some(mut current) = job.take() {
+        stop_job(&mut current, stop_policy).await?;
+        *job = Some(start_job(command, pid, lifecycle)?);
+    }
+    Ok(())
 }
 
-/// Reloads a job.
-#[cfg(target_family = "windows")]
-async fn reload_job(mut job: Job, command: &Command) -> (Job, u32) {
-    stop_job(&mut job).await;
-    start_job(command)
-}
+/// Verifies the candidate torrc, swapping it in and reloading the job only if Tor accepts it.
+///
+/// The torrc is left untouched if verification fails, and restored if the reload fails, so Tor
+/// keeps running with the last known good configuration.
+async fn reload_config(
+    job: &mut Option<Job>,
+    command: &Command,
+    candidate: Option<&Path>,
+    pid: &Pid,
+    stop_policy: &StopPolicy,
+    lifecycle: &mut Lifecycle,
+) -> Result<(), Error> {
+    if job.is_none() {
+        return Err(Error::NotRunning);
+    }
+
+    let torrc = command.options().torrc.as_deref();
 
-/// Returns true if reload has been requested.
-fn reload_requested(reload: &Arc<AtomicBool>) -> bool {
-    reload.swap(false, Ordering::Relaxed)
+    let backup = match (candidate, torrc) {
+        (Some(candidate), Some(torrc)) => {
+            // a newer candidate may be written while this one is verified
+            let snapshot = torrc::snapshot(candidate, torrc)?;
+            torrc::verify(command, snapshot.path()).await?;
+            Some(torrc::replace(snapshot.path(), torrc)?)
+        }
+        (Some(_), None) => {
+            return Err(Error::InvalidConfig(
+                "Command has no torrc to replace.".to_string(),
+            ))
+        }
+        (None, Some(torrc)) => {
+            torrc::verify(command, torrc).await?;
+            None
+        }
+        (None, None) => None,
+    };
+
+    let reloaded = reload_job(job, command, pid, stop_policy, lifecycle).await;
+
+    if let (Err(error), Some(backup)) = (&reloaded, backup) {
+        tracing::error!(%error, "failed to reload job, restoring last known good torrc");
+        backup.restore()?;
+    }
+
+    reloaded
 }
 
-/// Returns true if termination has been requested.
-fn termination_requested(terminate: &Arc<AtomicBool>) -> bool {
-    terminate.load(Ordering::Relaxed)
+/// Reloads a job.
+#[cfg(target_family = "unix")]
+async fn reload_job(
+    job: &mut Option<Job>,
+    _command: &Command,
+    _pid: &Pid,
+    _stop_policy: &StopPolicy,
+    lifecycle: &mut Lifecycle,
+) -> Result<(), Error> {
+    let current = job.as_ref().ok_or(Error::NotRunning)?;
+    let id = current.id().ok_or(Error::NotRunning)?;
+
+    lifecycle.emit(Event::Reloading);
+    match current.reload() {
+        Ok(()) => {
+            lifecycle.emit(Event::Reloaded);
+            Ok(())
+        }
+        Err(error) => {
+            lifecycle.emit(Event::Running { pid: id });
+            Err(error)
+        }
+    }
 }
 
-/// Returns true if the job has died.
-async fn job_died(job: &mut Job) -> bool {
-    job.status()
-        .await
-        .expect("Failed to query job status.")
-        .is_some()
+/// Reloads a job.
+#[cfg(target_family = "windows")]
+async fn reload_job(
+    job: &mut Option<Job>,
+    command: &Command,
+    pid: &Pid,
+    stop_policy: &StopPolicy,
+    lifecycle: &mut Lifecycle,
+) -> Result<(), Error> {
+    if job.is_none() {
+        return Err(Error::NotRunning);
+    }
+
+    lifecycle.emit(Event::Reloading);
+    restart_job(job, command, pid, stop_policy, lifecycle).await?;
+    lifecycle.emit(Event::Reloaded);
+    Ok(())
 }
 
-/// Sleeps for a constant duration.
-async fn sleep() {
-    tokio::time::sleep(Duration::from_millis(1000)).await;
+/// Completes with the exit status once the job has exited.
+async fn job_exited(job: &mut Option<Job>) -> Result<ExitStatus, Error> {
+    match job {
+        Some(job) => job.wait().await,
+        None => std::future::pending().await,
+    }
 }
 
+/// Reloads send SIGHUP and stops send SIGTERM, neither of which exist on Windows.
+#[cfg(target_family = "unix")]
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::restart_policy::RestartMode;
+    use crate::status::Status;
+    use fake::{Fake, Faker};
+    use tokio::sync::{broadcast, watch};
+    use tokio::time::sleep;
+
+    #[tokio::test]
+    async fn it_acknowledges_requests() {
+        // Arrange
+        let path = format!("test-{}.pid", Faker.fake::<String>());
+        let (statuses, status) = watch::channel(Status::default());
+        let (events, mut event) = broadcast::channel(16);
+        let (sender, receiver) = mpsc::channel(1);
+        let handle = tokio::spawn(event_loop(
+            create_command(),
+            Pid::new(&path),
+            RestartPolicy::default(),
+            StopPolicy::default(),
+            Duration::from_millis(0),
+            Lifecycle::new(Arc::new(statuses), events),
+            receiver,
+        ));
+
+        // Act
+        sleep(Duration::from_millis(50)).await;
+        let (ack, reloaded) = oneshot::channel();
+        sender.send(Request::Reload(None, ack)).await.unwrap();
+        let reloaded = reloaded.await.expect("Failed to acknowledge reload.");
+        let running = status.borrow().state;
 
-    #[test]
-    fn reload_requested_returns_true_if_requested() {
-        let flag = Arc::new(AtomicBool::new(true));
-        let requested = reload_requested(&flag);
+        sender.send(Request::Stop).await.unwrap();
+        let stopped = handle.await.expect("Failed to join event loop.");
 
-        assert_eq!(true, requested);
-        assert_eq!(
-            false,
-            flag.load(Ordering::Relaxed),
-            "Flag should always reset to false."
+        // Assert
+        assert_eq!(1, reloaded.expect("Failed to reload."));
+        assert!(stopped.expect("Failed to stop.").is_some());
+        assert_eq!(State::Running, running);
+        assert_eq!(State::Stopped, status.borrow().state);
+        assert_eq!(Ok(Event::Starting), event.try_recv());
+        assert!(matches!(event.try_recv(), Ok(Event::Running { .. })));
+        assert_eq!(Ok(Event::Reloading), event.try_recv());
+        assert_eq!(Ok(Event::Reloaded), event.try_recv());
+        assert!(
+            matches!(event.try_recv(), Ok(Event::Exited { output, .. }) if output.iter().any(|line| line.starts_with("Shutting down")))
         );
+        assert_eq!(Ok(Event::Stopped), event.try_recv());
     }
 
-    #[test]
-    fn reload_requested_returns_false_if_not_requested() {
-        let flag = Arc::new(AtomicBool::new(false));
-        let requested = reload_requested(&flag);
+    #[tokio::test]
+    async fn it_reloads_candidate_torrc_without_leaving_its_snapshot() {
+        // Arrange
+        let path = format!("test-{}.pid", Faker.fake::<String>());
+        let torrc = format!("test-{}.torrc", Faker.fake::<String>());
+        let candidate = format!("test-{}.torrc", Faker.fake::<String>());
+        std::fs::write(&torrc, "SocksPort 9050\n").expect("Failed to create test file.");
+        std::fs::write(&candidate, "SocksPort 9150\n").expect("Failed to create test file.");
+        let (statuses, _) = watch::channel(Status::default());
+        let (events, _) = broadcast::channel(16);
+        let (sender, receiver) = mpsc::channel(1);
+        let mut command = create_command();
+        command.arg("--no-wait").torrc(&torrc);
+        let handle = tokio::spawn(event_loop(
+            command,
+            Pid::new(&path),
+            RestartPolicy::default(),
+            StopPolicy::default(),
+            Duration::from_millis(0),
+            Lifecycle::new(Arc::new(statuses), events),
+            receiver,
+        ));
 
-        assert_eq!(false, requested);
-        assert_eq!(
-            false,
-            flag.load(Ordering::Relaxed),
-            "Flag should always reset to false."
-        );
+        // Act
+        let (ack, reloaded) = oneshot::channel();
+        sender
+            .send(Request::Reload(Some(PathBuf::from(&candidate)), ack))
+            .await
+            .unwrap();
+        let reloaded = reloaded.await.expect("Failed to acknowledge reload.");
+        sender.send(Request::Stop).await.unwrap();
+        handle.await.unwrap().expect("Failed to stop.");
+
+        // Assert
+        assert_eq!(1, reloaded.expect("Failed to reload."));
+        assert_eq!("SocksPort 9150\n", std::fs::read_to_string(&torrc).unwrap());
+        assert!(!Path::new(&format!("{}.verifying", torrc)).exists());
+        std::fs::remove_file(&torrc).expect("Failed to delete test file.");
+        std::fs::remove_file(&candidate).expect("Failed to delete test file.");
     }
 
-    #[test]
-    fn termination_requested_returns_true_if_requested() {
-        let flag = Arc::new(AtomicBool::new(true));
-        let requested = termination_requested(&flag);
+    #[tokio::test]
+    async fn it_coalesces_reloads_within_debounce_window() {
+        // Arrange
+        let path = format!("test-{}.pid", Faker.fake::<String>());
+        let (statuses, _) = watch::channel(Status::default());
+        let (events, mut event) = broadcast::channel(16);
+        let (sender, receiver) = mpsc::channel(4);
+        let handle = tokio::spawn(event_loop(
+            create_command(),
+            Pid::new(&path),
+            RestartPolicy::default(),
+            StopPolicy::default(),
+            Duration::from_millis(200),
+            Lifecycle::new(Arc::new(statuses), events),
+            receiver,
+        ));
 
-        assert_eq!(true, requested);
-        assert_eq!(
-            true,
-            flag.load(Ordering::Relaxed),
-            "Flag should not change."
-        );
+        // Act
+        sleep(Duration::from_millis(50)).await;
+        let mut acknowledgements = Vec::new();
+        for _ in 0..3 {
+            let (ack, reloaded) = oneshot::channel();
+            sender.send(Request::Reload(None, ack)).await.unwrap();
+            acknowledgements.push(reloaded);
+        }
+        let mut generations = Vec::new();
+        for reloaded in acknowledgements {
+            generations.push(reloaded.await.unwrap().expect("Failed to reload."));
+        }
+
+        sender.send(Request::Stop).await.unwrap();
+        handle.await.unwrap().expect("Failed to stop.");
+
+        // Assert
+        assert_eq!(vec![1, 1, 1], generations);
+        assert_eq!(Ok(Event::Starting), event.try_recv());
+        assert!(matches!(event.try_recv(), Ok(Event::Running { .. })));
+        assert_eq!(Ok(Event::Reloading), event.try_recv());
+        assert_eq!(Ok(Event::Reloaded), event.try_recv());
+        assert!(matches!(event.try_recv(), Ok(Event::Exited { .. })));
     }
 
-    #[test]
-    fn termination_requested_returns_false_if_not_requested() {
-        let flag = Arc::new(AtomicBool::new(false));
-        let requested = termination_requested(&flag);
+    #[tokio::test]
+    async fn it_changes_debounce_window() {
+        // Arrange
+        let path = format!("test-{}.pid", Faker.fake::<String>());
+        let (statuses, _) = watch::channel(Status::default());
+        let (events, _) = broadcast::channel(16);
+        let (sender, receiver) = mpsc::channel(4);
+        let handle = tokio::spawn(event_loop(
+            create_command(),
+            Pid::new(&path),
+            RestartPolicy::default(),
+            StopPolicy::default(),
+            Duration::from_millis(0),
+            Lifecycle::new(Arc::new(statuses), events),
+            receiver,
+        ));
 
-        assert_eq!(false, requested);
-        assert_eq!(
-            false,
-            flag.load(Ordering::Relaxed),
-            "Flag should not change."
+        // Act
+        sleep(Duration::from_millis(50)).await;
+        let (ack, debounced) = oneshot::channel();
+        sender
+            .send(Request::Debounce(Duration::from_millis(200), ack))
+            .await
+            .unwrap();
+        debounced
+            .await
+            .unwrap()
+            .expect("Failed to change debounce.");
+        let mut acknowledgements = Vec::new();
+        for _ in 0..2 {
+            let (ack, reloaded) = oneshot::channel();
+            sender.send(Request::Reload(None, ack)).await.unwrap();
+            acknowledgements.push(reloaded);
+        }
+        let mut generations = Vec::new();
+        for reloaded in acknowledgements {
+            generations.push(reloaded.await.unwrap().expect("Failed to reload."));
+        }
+
+        sender.send(Request::Stop).await.unwrap();
+        handle.await.unwrap().expect("Failed to stop.");
+
+        // Assert
+        assert_eq!(vec![1, 1], generations);
+    }
+
+    #[tokio::test]
+    async fn it_acknowledges_superseded_candidates_with_the_later_generation() {
+        // Arrange
+        let mut reloads = Reloads::default();
+        let (first, first_reloaded) = oneshot::channel();
+        let (second, second_reloaded) = oneshot::channel();
+        let error = Error::InvalidConfig(Faker.fake::<String>());
+        let message = error.to_string();
+
+        // Act
+        reloads.push(Some(PathBuf::from("first.torrc")), first);
+        reloads.push(Some(PathBuf::from("second.torrc")), second);
+        let (generation, candidate, acks) = reloads.take();
+        reloads.complete(generation, acks, Err(error));
+
+        // Assert
+        assert_eq!(Some(PathBuf::from("second.torrc")), candidate);
+        for reloaded in [first_reloaded.await, second_reloaded.await].iter() {
+            match reloaded {
+                Ok(Err(Error::Reload { generation, error })) => {
+                    assert_eq!(1, *generation);
+                    assert_eq!(message, error.to_string());
+                }
+                _ => panic!("Expected the reload of generation 1 to fail."),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn it_stops_when_requests_are_closed() {
+        // Arrange
+        let path = format!("test-{}.pid", Faker.fake::<String>());
+        let (statuses, status) = watch::channel(Status::default());
+        let (events, _) = broadcast::channel(16);
+        let (sender, receiver) = mpsc::channel(1);
+        let handle = tokio::spawn(event_loop(
+            create_command(),
+            Pid::new(&path),
+            RestartPolicy::default(),
+            StopPolicy::default(),
+            Duration::from_millis(0),
+            Lifecycle::new(Arc::new(statuses), events),
+            receiver,
+        ));
+
+        // Act
+        std::mem::drop(sender);
+        let stopped = handle.await.expect("Failed to join event loop.");
+
+        // Assert
+        assert!(stopped.is_ok());
+        assert_eq!(State::Stopped, status.borrow().state);
+    }
+
+    #[tokio::test]
+    async fn it_restarts_job_until_crash_looping() {
+        // Arrange
+        let path = format!("test-{}.pid", Faker.fake::<String>());
+        let (statuses, status) = watch::channel(Status::default());
+        let (events, mut event) = broadcast::channel(32);
+        let (_sender, receiver) = mpsc::channel(1);
+        let mut command = create_command();
+        command
+            .arg("--no-wait")
+            .arg("--exit-after")
+            .arg("0.1")
+            .arg("--exit-code")
+            .arg("3");
+        let restart_policy = RestartPolicy::new(
+            RestartMode::Always,
+            2,
+            Duration::from_secs(60),
+            Duration::from_millis(10),
+            Duration::from_millis(10),
         );
+
+        // Act
+        let stopped = event_loop(
+            command,
+            Pid::new(&path),
+            restart_policy,
+            StopPolicy::default(),
+            Duration::from_millis(0),
+            Lifecycle::new(Arc::new(statuses), events),
+            receiver,
+        )
+        .await;
+        let events: Vec<Event> = std::iter::from_fn(|| event.try_recv().ok()).collect();
+
+        // Assert
+        assert!(stopped.expect("Failed to stop.").is_none());
+        assert_eq!(State::Failed, status.borrow().state);
+        let exits: Vec<&Vec<String>> = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Exited { status, output } if status.code() == Some(3) => Some(output),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(3, exits.len());
+        assert!(exits.iter().all(|output| output
+            .iter()
+            .any(|line| line.ends_with("Exiting with code 3 as requested."))));
+        assert!(events.contains(&Event::Restarting { attempt: 1 }));
+        assert!(events.contains(&Event::Restarting { attempt: 2 }));
+        assert_eq!(Some(&Event::Failed), events.last());
+    }
+
+    #[tokio::test]
+    async fn it_fails_pending_reloads_when_job_crashes_on_reload() {
+        // Arrange
+        let path = format!("test-{}.pid", Faker.fake::<String>());
+        let (statuses, _) = watch::channel(Status::default());
+        let (events, mut event) = broadcast::channel(16);
+        let (sender, receiver) = mpsc::channel(1);
+        let mut command = create_command();
+        command.arg("--no-wait").arg("--crash-on-reload").arg("1");
+        let handle = tokio::spawn(event_loop(
+            command,
+            Pid::new(&path),
+            RestartPolicy::new(
+                RestartMode::Never,
+                0,
+                Duration::from_secs(60),
+                Duration::from_millis(10),
+                Duration::from_millis(10),
+            ),
+            StopPolicy::default(),
+            Duration::from_millis(0),
+            Lifecycle::new(Arc::new(statuses), events),
+            receiver,
+        ));
+
+        // Act
+        sleep(Duration::from_millis(100)).await;
+        let (ack, crashing) = oneshot::channel();
+        sender.send(Request::Reload(None, ack)).await.unwrap();
+        let crashing = crashing.await.expect("Failed to acknowledge reload.");
+        let (ack, debounced) = oneshot::channel();
+        sender
+            .send(Request::Debounce(Duration::from_secs(5), ack))
+            .await
+            .unwrap();
+        debounced
+            .await
+            .unwrap()
+            .expect("Failed to change debounce.");
+        let (ack, pending) = oneshot::channel();
+        sender.send(Request::Reload(None, ack)).await.unwrap();
+        let pending = pending.await.expect("Failed to acknowledge reload.");
+        let stopped = handle.await.expect("Failed to join event loop.");
+        let events: Vec<Event> = std::iter::from_fn(|| event.try_recv().ok()).collect();
+
+        // Assert
+        assert_eq!(1, crashing.expect("Failed to signal reload."));
+        assert!(matches!(
+            pending,
+            Err(Error::Reload { generation: 2, error }) if matches!(*error, Error::ExitedBeforeReload(_))
+        ));
+        assert!(stopped.expect("Failed to stop.").is_none());
+        assert!(events.iter().any(|event| matches!(
+            event,
+            Event::Exited { status, output }
+                if !status.success()
+                    && output.iter().any(|line| line.ends_with("Crashing on reload 1 as requested."))
+        )));
+        assert_eq!(Some(&Event::Failed), events.last());
+    }
+
+    fn create_command() -> Command {
+        let path = std::env::current_dir()
+            .unwrap()
+            .join("../target/debug/tor-stub");
+
+        if !std::path::Path::new(&path).exists() {
+            panic!("tor-stub does not exist. Please run cargo build --workspace then try again.");
+        }
+        Command::new(path.to_str().unwrap(), false)
     }
 }
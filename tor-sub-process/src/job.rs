@@ -1,10 +1,21 @@
-use std::process::{ExitStatus, Output};
+use crate::error::Error;
+use crate::output_buffer::OutputBuffer;
+use crate::signal;
+use crate::stop_policy::{Escalation, StopPolicy, Termination};
+use crate::tor_log::{self, Severity};
+use std::process::{ExitStatus, Output, Stdio};
+use tokio::io::{AsyncRead, AsyncReadExt};
 use tokio::process::{Child, Command};
+use tokio::task::JoinHandle;
+use tokio::time::{timeout, Duration};
 
 /// Represents a child process as a single unit of work.
 pub struct Job {
     command: Command,
     child: Option<Child>,
+    forward_output: bool,
+    output: OutputBuffer,
+    forwarders: Vec<JoinHandle<()>>,
 }
 
 impl Job {
@@ -12,53 +23,109 @@ impl Job {
         Self {
             command,
             child: None,
+            forward_output: false,
+            output: OutputBuffer::new(0),
+            forwarders: Vec::new(),
         }
     }
 
+    /// Pipes stdout and stderr of the job into `tracing` events under the `tor` target, keeping
+    /// the last `lines` lines of each run.
+    pub fn forward_output(mut self, lines: usize) -> Self {
+        self.command.stdout(Stdio::piped()).stderr(Stdio::piped());
+        self.forward_output = true;
+        self.output = OutputBuffer::new(lines);
+        self
+    }
+
     /// Starts the job as a child process.
-    pub fn start(&mut self) -> Result<(), std::io::Error> {
-        if self.child.is_none() {
-            let child = self.command.spawn()?;
-            self.child = Some(child);
-            return Ok(());
+    pub fn start(&mut self) -> Result<(), Error> {
+        if self.child.is_some() {
+            return Err(Error::AlreadyRunning);
         }
 
-        unimplemented!();
+        let mut child = self.command.spawn().map_err(Error::Spawn)?;
+        if self.forward_output {
+            self.output = OutputBuffer::new(self.output.capacity());
+            self.forwarders = forward_output(&mut child, &self.output);
+        }
+        self.child = Some(child);
+        Ok(())
     }
 
-    /// Waits for the job to exit completely, returning the status that it exited with.
-    pub async fn stop(&mut self) -> Result<Output, std::io::Error> {
-        if let Some(child) = self.child.take() {
-            if let Some(id) = child.id() {
-                signal::sigterm(id);
+    /// Waits for the job to exit completely, escalating signals according to `stop_policy`,
+    /// returning the status that it exited with.
+    pub async fn stop(&mut self, stop_policy: &StopPolicy) -> Result<Termination, Error> {
+        let mut child = self.child.take().ok_or(Error::NotRunning)?;
+        let stdout = read_to_end(child.stdout.take());
+        let stderr = read_to_end(child.stderr.take());
+
+        let group = stop_policy.process_group();
+        let mut escalation = Escalation::Sigterm;
+        let status = match child.id() {
+            Some(id) => {
+                signal::escalate(id, Escalation::Sigterm, group).map_err(Error::Signal)?;
+                match timeout(stop_policy.terminate_timeout(), child.wait()).await {
+                    Ok(status) => status?,
+                    Err(_) => {
+                        escalation = Escalation::Sigint;
+                        tracing::warn!(pid = id, "job ignored SIGTERM, sending SIGINT");
+                        signal::escalate(id, Escalation::Sigint, group).map_err(Error::Signal)?;
+                        match timeout(stop_policy.interrupt_timeout(), child.wait()).await {
+                            Ok(status) => status?,
+                            Err(_) => {
+                                escalation = Escalation::Sigkill;
+                                tracing::warn!(pid = id, "job ignored SIGINT, sending SIGKILL");
+                                if group {
+                                    signal::escalate(id, Escalation::Sigkill, group)
+                                        .map_err(Error::Signal)?;
+                                }
+                                child.kill().await.map_err(Error::Signal)?;
+                                child.wait().await?
+                            }
+                        }
+                    }
+                }
             }
-            let output = child.wait_with_output().await?;
-            return Ok(output);
-        }
+            None => child.wait().await?,
+        };
+
+        let output = Output {
+            status,
+            stdout: stdout.await.map_err(|error| Error::Io(error.into()))??,
+            stderr: stderr.await.map_err(|error| Error::Io(error.into()))??,
+        };
 
-        unimplemented!();
+        Ok(Termination { escalation, output })
     }
 
-    /// Attempts to collect the exit status of the job if it has already exited.
-    pub async fn status(&mut self) -> Result<Option<ExitStatus>, std::io::Error> {
-        if let Some(child) = self.child.as_mut() {
-            return child.try_wait();
-        }
+    /// Waits for the job to exit, returning the status that it exited with.
+    pub async fn wait(&mut self) -> Result<ExitStatus, Error> {
+        let child = self.child.as_mut().ok_or(Error::NotRunning)?;
+        Ok(child.wait().await?)
+    }
 
-        unimplemented!();
+    /// Attempts to collect the exit status of the job if it has already exited.
+    #[allow(dead_code)]
+    pub async fn status(&mut self) -> Result<Option<ExitStatus>, Error> {
+        let child = self.child.as_mut().ok_or(Error::NotRunning)?;
+        Ok(child.try_wait()?)
     }
 
     /// Sends a reload signal to the job.
     #[cfg(target_family = "unix")]
-    pub fn reload(&self) {
-        if let Some(child) = &self.child {
-            if let Some(id) = child.id() {
-                signal::sighup(id);
-                return;
-            }
-        }
+    pub fn reload(&self) -> Result<(), Error> {
+        let id = self.id().ok_or(Error::NotRunning)?;
+        signal::sighup(id).map_err(Error::Signal)
+    }
 
-        unimplemented!();
+    /// Returns the last lines of output of the current run, giving the forwarders a moment to
+    /// drain output written just before the job exited.
+    pub async fn recent_output(&mut self) -> Vec<String> {
+        for forwarder in self.forwarders.drain(..) {
+            let _ = timeout(Duration::from_millis(100), forwarder).await;
+        }
+        self.output.lines()
     }
 
     /// Gets the process id of the running job.
@@ -67,28 +134,43 @@ impl Job {
     }
 }
 
-/// Safe wrappers for libc interfaces
-mod signal {
-    #[cfg(target_family = "unix")]
-    pub fn sighup(id: u32) {
-        unsafe {
-            libc::kill(id as i32, signal_hook::consts::signal::SIGHUP);
-        }
+/// Spawns tasks forwarding the output of the child process into `tracing` and `buffer`.
+fn forward_output(child: &mut Child, buffer: &OutputBuffer) -> Vec<JoinHandle<()>> {
+    let id = child.id().unwrap_or_default();
+    let mut forwarders = Vec::new();
+
+    if let Some(stdout) = child.stdout.take() {
+        forwarders.push(tokio::spawn(tor_log::forward(
+            stdout,
+            id,
+            Severity::Notice,
+            buffer.clone(),
+        )));
     }
 
-    #[cfg(target_family = "unix")]
-    pub fn sigterm(id: u32) {
-        unsafe {
-            libc::kill(id as i32, signal_hook::consts::signal::SIGTERM);
-        }
+    if let Some(stderr) = child.stderr.take() {
+        forwarders.push(tokio::spawn(tor_log::forward(
+            stderr,
+            id,
+            Severity::Warn,
+            buffer.clone(),
+        )));
     }
 
-    #[cfg(target_family = "windows")]
-    pub fn sigterm(id: u32) {
-        unsafe {
-            libc::signal(signal_hook::consts::signal::SIGTERM, id as usize);
+    forwarders
+}
+
+/// Spawns a task reading the stream to its end.
+fn read_to_end(
+    stream: Option<impl AsyncRead + Send + Unpin + 'static>,
+) -> JoinHandle<Result<Vec<u8>, std::io::Error>> {
+    tokio::spawn(async move {
+        let mut buffer = Vec::new();
+        if let Some(mut stream) = stream {
+            stream.read_to_end(&mut buffer).await?;
         }
-    }
+        Ok(buffer)
+    })
 }
 
 /// Windows platform swallows signals from `cargo test` making it difficult for `Job` to send
@@ -98,7 +180,7 @@ mod signal {
 mod tests {
     use super::*;
     use std::process::Stdio;
-    use tokio::time::{sleep, Duration};
+    use tokio::time::sleep;
 
     #[tokio::test]
     async fn it_can_send_signals() {
@@ -108,13 +190,18 @@ mod tests {
         // Act
         job.start().expect("Failed to start job.");
         sleep(Duration::from_millis(50)).await;
-        job.reload();
+        job.reload().expect("Failed to reload job.");
         sleep(Duration::from_millis(20)).await;
-        job.reload();
+        job.reload().expect("Failed to reload job.");
         sleep(Duration::from_millis(20)).await;
-        let output = job.stop().await.expect("Failed to start job.");
+        let termination = job
+            .stop(&StopPolicy::default())
+            .await
+            .expect("Failed to start job.");
+        let output = termination.output;
 
         // Assert
+        assert_eq!(Escalation::Sigterm, termination.escalation);
         assert_eq!(Some(0), output.status.code());
         assert_eq!(
             r#"
@@ -139,7 +226,9 @@ Shutting down.
         job.start().expect("Failed to start job.");
         sleep(Duration::from_millis(1000)).await;
         let running_status = job.status().await.expect("Failed to get status.");
-        job.stop().await.expect("Failed to start job.");
+        job.stop(&StopPolicy::default())
+            .await
+            .expect("Failed to start job.");
 
         // Assert
         assert_eq!(None, running_status);
@@ -156,7 +245,9 @@ Shutting down.
         job.start().expect("Failed to start job.");
         sleep(Duration::from_millis(1000)).await;
         let running_status = job.status().await.expect("Failed to get status.");
-        job.stop().await.expect("Failed to start job.");
+        job.stop(&StopPolicy::default())
+            .await
+            .expect("Failed to start job.");
 
         // Assert
         assert_eq!(0, running_status.unwrap().code().unwrap());
@@ -173,12 +264,167 @@ Shutting down.
         job.start().expect("Failed to start job.");
         sleep(Duration::from_millis(1000)).await;
         let running_status = job.status().await.expect("Failed to get status.");
-        job.stop().await.expect("Failed to start job.");
+        job.stop(&StopPolicy::default())
+            .await
+            .expect("Failed to start job.");
 
         // Assert
         assert_eq!(127, running_status.unwrap().code().unwrap());
     }
 
+    #[tokio::test]
+    async fn it_rejects_operations_in_unexpected_state() {
+        // Arrange
+        let mut job = create_job();
+
+        // Act
+        let not_running = job.stop(&StopPolicy::default()).await;
+        job.start().expect("Failed to start job.");
+        let already_running = job.start();
+        job.stop(&StopPolicy::default())
+            .await
+            .expect("Failed to stop job.");
+
+        // Assert
+        assert!(matches!(not_running, Err(Error::NotRunning)));
+        assert!(matches!(already_running, Err(Error::AlreadyRunning)));
+    }
+
+    #[tokio::test]
+    async fn it_escalates_signals_when_ignored() {
+        // Arrange
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("trap '' TERM INT; exec sleep 10");
+        let mut job = Job::new(command);
+        let stop_policy = StopPolicy::new(Duration::from_millis(100), Duration::from_millis(100));
+
+        // Act
+        job.start().expect("Failed to start job.");
+        sleep(Duration::from_millis(100)).await;
+        let termination = job.stop(&stop_policy).await.expect("Failed to stop job.");
+
+        // Assert
+        assert_eq!(Escalation::Sigkill, termination.escalation);
+        assert_eq!(None, termination.output.status.code());
+    }
+
+    #[tokio::test]
+    async fn it_can_signal_process_group() {
+        // Arrange
+        let mut command = crate::command::Command::new("sh", false);
+        command.arg("-c").arg("sleep 10 & echo $!; wait");
+        let mut job = Job::new(command.create());
+        job.command.stdout(Stdio::piped());
+        let stop_policy = StopPolicy::default().with_process_group(true);
+
+        // Act
+        job.start().expect("Failed to start job.");
+        sleep(Duration::from_millis(100)).await;
+        let termination = job.stop(&stop_policy).await.expect("Failed to stop job.");
+
+        // Assert
+        let grandchild: u32 = String::from_utf8_lossy(&termination.output.stdout)
+            .trim()
+            .parse()
+            .expect("Failed to read grandchild PID.");
+        let mut attempts = 0;
+        while running(grandchild) && attempts < 10 {
+            sleep(Duration::from_millis(100)).await;
+            attempts += 1;
+        }
+        assert_eq!(Escalation::Sigterm, termination.escalation);
+        assert!(!running(grandchild));
+    }
+
+    /// Returns true if the process exists and is not a zombie awaiting its parent.
+    fn running(id: u32) -> bool {
+        match std::fs::read_to_string(format!("/proc/{}/stat", id)) {
+            Ok(stat) => !stat
+                .rsplit(')')
+                .next()
+                .unwrap_or_default()
+                .trim_start()
+                .starts_with('Z'),
+            Err(_) => false,
+        }
+    }
+
+    #[tokio::test]
+    async fn it_keeps_recent_output_of_run() {
+        // Arrange
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg("echo one; echo two >&2; echo three; exit 3");
+        let mut job = Job::new(command).forward_output(2);
+
+        // Act
+        job.start().expect("Failed to start job.");
+        let status = job.wait().await.expect("Failed to wait for job.");
+        let output = job.recent_output().await;
+        let again = job.recent_output().await;
+
+        // Assert
+        assert_eq!(Some(3), status.code());
+        assert_eq!(2, output.len());
+        assert!(output.contains(&"three".to_string()));
+        assert_eq!(output, again);
+    }
+
+    #[tokio::test]
+    async fn it_escalates_to_sigint_when_tor_ignores_sigterm() {
+        // Arrange
+        let mut job = create_job_with(&["--ignore-sigterm"]);
+        let stop_policy = StopPolicy::new(Duration::from_millis(200), Duration::from_secs(5));
+
+        // Act
+        job.start().expect("Failed to start job.");
+        sleep(Duration::from_millis(100)).await;
+        let termination = job.stop(&stop_policy).await.expect("Failed to stop job.");
+
+        // Assert
+        assert_eq!(Escalation::Sigint, termination.escalation);
+        assert_eq!(Some(0), termination.output.status.code());
+    }
+
+    #[tokio::test]
+    async fn it_escalates_to_sigkill_when_tor_hangs_during_shutdown() {
+        // Arrange
+        let mut job = create_job_with(&["--hang-on-shutdown"]);
+        let stop_policy = StopPolicy::new(Duration::from_millis(200), Duration::from_millis(200));
+
+        // Act
+        job.start().expect("Failed to start job.");
+        sleep(Duration::from_millis(100)).await;
+        let termination = job.stop(&stop_policy).await.expect("Failed to stop job.");
+
+        // Assert
+        assert_eq!(Escalation::Sigkill, termination.escalation);
+        assert_eq!(None, termination.output.status.code());
+        assert!(String::from_utf8_lossy(&termination.output.stdout)
+            .contains("Catching signal TERM, exiting cleanly."));
+    }
+
+    #[tokio::test]
+    async fn it_keeps_output_of_tor_writing_garbage() {
+        // Arrange
+        let mut job =
+            create_job_with(&["--garbage-output", "--exit-after", "0.1"]).forward_output(100);
+
+        // Act
+        job.start().expect("Failed to start job.");
+        let status = job.wait().await.expect("Failed to wait for job.");
+        let output = job.recent_output().await;
+
+        // Assert
+        assert_eq!(Some(1), status.code());
+        assert!(output.iter().any(|line| line.contains("not a log line")));
+        assert!(output
+            .last()
+            .unwrap()
+            .ends_with("Exiting with code 1 as requested."));
+    }
+
     fn create_job() -> Job {
         let path = std::env::current_dir()
             .unwrap()
@@ -191,4 +437,10 @@ Shutting down.
         command.arg("--no-wait").stdout(Stdio::piped());
         Job::new(command)
     }
+
+    fn create_job_with(args: &[&str]) -> Job {
+        let mut job = create_job();
+        job.command.args(args);
+        job
+    }
 }
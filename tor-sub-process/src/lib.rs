@@ -1,9 +1,32 @@
 mod command;
 mod controller;
+mod controller_builder;
+mod error;
 mod event_loop;
 mod job;
+mod lifecycle;
+mod orphan;
+mod output_buffer;
 mod pid;
+mod privileges;
+mod restart_policy;
 mod scheduler;
+mod signal;
+mod status;
+mod stop_policy;
+mod tor_log;
+mod tor_options;
+mod torrc;
 
-pub use command::Command;
+pub use command::{Command, StdioPolicy};
 pub use controller::Controller;
+pub use controller_builder::ControllerBuilder;
+pub use error::Error;
+pub use lifecycle::Event;
+pub use orphan::OrphanPolicy;
+pub use restart_policy::{RestartMode, RestartPolicy};
+pub use scheduler::PendingReload;
+pub use status::{State, Status};
+pub use stop_policy::{Escalation, StopPolicy, Termination};
+pub use tor_log::Severity;
+pub use tor_options::{ControlPort, TorOptions};
@@ -0,0 +1,125 @@
+use crate::status::{State, Status};
+use std::process::ExitStatus;
+use std::sync::Arc;
+use tokio::sync::{broadcast, watch};
+
+/// Transitions in the lifecycle of a scheduled job.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    Starting,
+    Running {
+        pid: u32,
+    },
+    Reloading,
+    Reloaded,
+    Exited {
+        status: ExitStatus,
+        /// Last lines of output of the run.
+        output: Vec<String>,
+    },
+    Restarting {
+        attempt: u32,
+    },
+    Failed,
+    Stopped,
+}
+
+/// Publishes lifecycle events and the resulting status to observers.
+pub struct Lifecycle {
+    status: Status,
+    statuses: Arc<watch::Sender<Status>>,
+    events: broadcast::Sender<Event>,
+}
+
+impl Lifecycle {
+    pub fn new(statuses: Arc<watch::Sender<Status>>, events: broadcast::Sender<Event>) -> Self {
+        Self {
+            status: Status::default(),
+            statuses,
+            events,
+        }
+    }
+
+    /// Returns the current status.
+    pub fn status(&self) -> &Status {
+        &self.status
+    }
+
+    /// Applies the event to the status then publishes both.
+    pub fn emit(&mut self, event: Event) {
+        tracing::debug!(?event, "lifecycle event");
+
+        apply(&mut self.status, &event);
+
+        // Observers are optional, sending only fails when there are none.
+        let _ = self.statuses.send(self.status.clone());
+        let _ = self.events.send(event);
+    }
+}
+
+/// Applies the event to the status.
+fn apply(status: &mut Status, event: &Event) {
+    match event {
+        Event::Starting => status.state = State::Starting,
+        Event::Running { .. } => status.state = State::Running,
+        Event::Reloading => status.state = State::Reloading,
+        Event::Reloaded => status.state = State::Running,
+        Event::Exited {
+            status: exit_status,
+            output,
+        } => {
+            status.last_exit_code = exit_status.code();
+            status.last_output = output.clone();
+        }
+        Event::Restarting { attempt } => {
+            status.state = State::Restarting;
+            status.restarts = *attempt;
+        }
+        Event::Failed => status.state = State::Failed,
+        Event::Stopped => status.state = State::Stopped,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply_transitions_state() {
+        let mut status = Status::default();
+
+        apply(&mut status, &Event::Starting);
+        assert_eq!(State::Starting, status.state);
+
+        apply(&mut status, &Event::Running { pid: 1 });
+        assert_eq!(State::Running, status.state);
+
+        apply(&mut status, &Event::Reloading);
+        assert_eq!(State::Reloading, status.state);
+
+        apply(&mut status, &Event::Reloaded);
+        assert_eq!(State::Running, status.state);
+
+        apply(&mut status, &Event::Restarting { attempt: 2 });
+        assert_eq!(State::Restarting, status.state);
+        assert_eq!(2, status.restarts);
+
+        apply(&mut status, &Event::Failed);
+        assert_eq!(State::Failed, status.state);
+
+        apply(&mut status, &Event::Stopped);
+        assert_eq!(State::Stopped, status.state);
+    }
+
+    #[test]
+    fn emit_publishes_event_and_status() {
+        let (statuses, status) = watch::channel(Status::default());
+        let (events, mut event) = broadcast::channel(1);
+        let mut lifecycle = Lifecycle::new(Arc::new(statuses), events);
+
+        lifecycle.emit(Event::Running { pid: 1 });
+
+        assert_eq!(State::Running, status.borrow().state);
+        assert_eq!(Ok(Event::Running { pid: 1 }), event.try_recv());
+    }
+}
@@ -0,0 +1,123 @@
+use crate::error::Error;
+use crate::stop_policy::{Escalation, StopPolicy};
+use std::time::Duration;
+
+/// Time allowed for a process to disappear after SIGKILL.
+#[cfg(target_family = "unix")]
+const KILL_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Determines how a Tor process left running by an unexpected shutdown is handled on start.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OrphanPolicy {
+    /// Refuses to start while the orphaned process is running.
+    #[default]
+    Refuse,
+    /// Stops the orphaned process before starting.
+    Reap,
+}
+
+/// Stops an orphaned process, escalating signals according to `stop_policy`.
+///
+/// The process is not a child so it cannot be waited on, instead it is polled until it is gone.
+#[cfg(target_family = "unix")]
+pub async fn reap(id: u32, stop_policy: &StopPolicy) -> Result<Escalation, Error> {
+    use crate::signal;
+
+    let group = stop_policy.process_group();
+
+    signal::escalate(id, Escalation::Sigterm, group).map_err(Error::Signal)?;
+    if exited(id, stop_policy.terminate_timeout()).await {
+        return Ok(Escalation::Sigterm);
+    }
+
+    signal::escalate(id, Escalation::Sigint, group).map_err(Error::Signal)?;
+    if exited(id, stop_policy.interrupt_timeout()).await {
+        return Ok(Escalation::Sigint);
+    }
+
+    signal::escalate(id, Escalation::Sigkill, group).map_err(Error::Signal)?;
+    if exited(id, KILL_TIMEOUT).await {
+        return Ok(Escalation::Sigkill);
+    }
+
+    // a killed process lingers as a zombie until its parent reaps it, which never happens when
+    // its parent exited and it was reparented to a PID 1 which does not reap
+    Err(Error::Signal(std::io::Error::new(
+        std::io::ErrorKind::TimedOut,
+        format!(
+            "Process {} still exists {:?} after SIGKILL, it may be a zombie.",
+            id, KILL_TIMEOUT
+        ),
+    )))
+}
+
+#[cfg(target_family = "windows")]
+pub async fn reap(_id: u32, _stop_policy: &StopPolicy) -> Result<Escalation, Error> {
+    Err(Error::Signal(std::io::Error::new(
+        std::io::ErrorKind::Other,
+        "Reaping orphaned processes is not supported on Windows.",
+    )))
+}
+
+/// Polls until the process is gone, returning false if it is still running after `timeout`.
+#[cfg(target_family = "unix")]
+async fn exited(id: u32, timeout: Duration) -> bool {
+    let deadline = tokio::time::Instant::now() + timeout;
+
+    while crate::signal::exists(id) {
+        if tokio::time::Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    true
+}
+
+#[cfg(target_family = "unix")]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn reap_stops_process() {
+        // Arrange
+        let mut child = std::process::Command::new("sh")
+            .arg("-c")
+            .arg("trap '' TERM INT; exec sleep 10")
+            .spawn()
+            .expect("Failed to spawn process.");
+        let id = child.id();
+        let stop_policy = StopPolicy::new(Duration::from_millis(200), Duration::from_millis(200));
+        // Collects the exit status so the process disappears as an orphan would once it exits.
+        std::thread::spawn(move || child.wait());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // Act
+        let escalation = reap(id, &stop_policy).await;
+
+        // Assert
+        assert_eq!(Escalation::Sigkill, escalation.expect("Failed to reap."));
+    }
+
+    #[tokio::test]
+    async fn reap_gives_up_on_zombie() {
+        // Arrange
+        let mut child = std::process::Command::new("sh")
+            .arg("-c")
+            .arg("trap '' TERM INT; exec sleep 10")
+            .spawn()
+            .expect("Failed to spawn process.");
+        let id = child.id();
+        let stop_policy = StopPolicy::new(Duration::from_millis(0), Duration::from_millis(0));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // Act
+        // The exit status is not collected so the killed process remains a zombie.
+        let escalation = reap(id, &stop_policy).await;
+        child.wait().expect("Failed to wait.");
+
+        // Assert
+        assert!(matches!(escalation, Err(Error::Signal(_))));
+    }
+}
@@ -0,0 +1,73 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// Keeps the most recent lines of output of a job run, discarding the oldest once full.
+#[derive(Clone, Debug)]
+pub struct OutputBuffer {
+    lines: Arc<Mutex<VecDeque<String>>>,
+    capacity: usize,
+}
+
+impl OutputBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            lines: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    /// Maximum number of lines kept.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Appends a line, discarding the oldest line if the buffer is full.
+    pub fn push(&self, line: &str) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut lines = self.lines.lock().unwrap_or_else(|error| error.into_inner());
+        if lines.len() == self.capacity {
+            lines.pop_front();
+        }
+        lines.push_back(line.to_string());
+    }
+
+    /// Returns a copy of the buffered lines, oldest first.
+    pub fn lines(&self) -> Vec<String> {
+        let lines = self.lines.lock().unwrap_or_else(|error| error.into_inner());
+        lines.iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_discards_oldest_lines_once_full() {
+        // Arrange
+        let buffer = OutputBuffer::new(2);
+
+        // Act
+        buffer.push("one");
+        buffer.push("two");
+        buffer.push("three");
+
+        // Assert
+        assert_eq!(vec!["two", "three"], buffer.lines());
+    }
+
+    #[test]
+    fn push_keeps_nothing_without_capacity() {
+        // Arrange
+        let buffer = OutputBuffer::new(0);
+
+        // Act
+        buffer.push("one");
+
+        // Assert
+        assert!(buffer.lines().is_empty());
+    }
+}
@@ -1,44 +1,160 @@
+use crate::signal;
+use std::fs::File;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Process recorded in a PID file.
+#[derive(Debug, PartialEq)]
+pub enum Recorded {
+    /// No process is recorded.
+    None,
+    /// The recorded process is no longer running, or its id has been reused by another program.
+    Stale(u32),
+    /// The recorded process is still running.
+    Running(u32),
+}
+
 pub struct Pid {
     path: String,
+    lock: Option<File>,
+    written: AtomicBool,
 }
 
 impl Pid {
     pub fn new(path: &str) -> Self {
         Self {
             path: path.to_string(),
+            lock: None,
+            written: AtomicBool::new(false),
         }
     }
 
+    /// Takes an exclusive advisory lock on the PID file, held until the `Pid` is dropped.
+    ///
+    /// The lock is taken on a sibling `.lock` file as the PID file itself is replaced on every
+    /// update. The `.lock` file is left in place, unlinking it would let another process lock a
+    /// new file while this one is still held.
+    pub fn lock(&mut self) -> Result<(), std::io::Error> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.lock_path())?;
+
+        flock(&file).map_err(|error| match error.kind() {
+            std::io::ErrorKind::WouldBlock => std::io::Error::new(
+                std::io::ErrorKind::WouldBlock,
+                format!("PID file '{}' is locked by another process.", self.path),
+            ),
+            _ => error,
+        })?;
+
+        self.lock = Some(file);
+        Ok(())
+    }
+
     pub fn read(&self) -> Result<Option<u32>, std::io::Error> {
-        if !std::path::Path::new(&self.path).exists() {
+        if !Path::new(&self.path).exists() {
             return Ok(None);
         }
 
-        let pid = std::fs::read_to_string(&self.path)?
+        let content = std::fs::read_to_string(&self.path)?;
+        let pid = content
+            .trim()
             .parse::<u32>()
-            .expect("Failed to parse PID.");
-        Ok(Some(pid))
+            .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))?;
+
+        match pid {
+            0 => Ok(None),
+            pid => Ok(Some(pid)),
+        }
+    }
+
+    /// Reads the PID file and checks whether the recorded process is still running `program`.
+    pub fn inspect(&self, program: &str) -> Result<Recorded, std::io::Error> {
+        match self.read()? {
+            None => Ok(Recorded::None),
+            Some(id) if is_running(id, program) => Ok(Recorded::Running(id)),
+            Some(id) => Ok(Recorded::Stale(id)),
+        }
     }
 
     pub fn update(&self, pid: u32) -> Result<(), std::io::Error> {
-        std::fs::write(&self.path, pid.to_string())?;
-        Ok(())
+        self.write(&pid.to_string())
     }
 
     pub fn reset(&self) -> Result<(), std::io::Error> {
-        std::fs::write(&self.path, "0")?;
+        self.write("0")
+    }
+
+    /// Writes the PID file atomically by renaming a temporary file over it.
+    fn write(&self, content: &str) -> Result<(), std::io::Error> {
+        let temporary = format!("{}.tmp", self.path);
+        std::fs::write(&temporary, content)?;
+        std::fs::rename(&temporary, &self.path)?;
+        self.written.store(true, Ordering::Relaxed);
         Ok(())
     }
+
+    fn lock_path(&self) -> String {
+        format!("{}.lock", self.path)
+    }
 }
 
+/// Deletes the PID file if it was written by this `Pid`, leaving files recording other processes.
 impl Drop for Pid {
     fn drop(&mut self) {
-        if std::path::Path::new(&self.path).exists() {
-            std::fs::remove_file(&self.path).expect("Failed to delete PID file.");
+        if self.written.load(Ordering::Relaxed) && Path::new(&self.path).exists() {
+            if let Err(error) = std::fs::remove_file(&self.path) {
+                tracing::warn!(%error, path = %self.path, "failed to delete PID file");
+            }
         }
     }
 }
 
+/// Takes an exclusive advisory lock without blocking.
+#[cfg(target_family = "unix")]
+fn flock(file: &File) -> Result<(), std::io::Error> {
+    use std::os::unix::io::AsRawFd;
+
+    match unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } {
+        0 => Ok(()),
+        _ => Err(std::io::Error::last_os_error()),
+    }
+}
+
+#[allow(clippy::unnecessary_wraps)]
+#[cfg(target_family = "windows")]
+fn flock(_file: &File) -> Result<(), std::io::Error> {
+    Ok(())
+}
+
+/// Returns true if the process is alive and, where `/proc` is available, executing `program`.
+#[cfg(target_family = "unix")]
+fn is_running(id: u32, program: &str) -> bool {
+    if !signal::exists(id) {
+        return false;
+    }
+
+    let proc = Path::new("/proc").join(id.to_string());
+    if !Path::new("/proc/self").exists() {
+        return true;
+    }
+
+    let program = Path::new(program).file_name();
+    match std::fs::read_link(proc.join("exe")) {
+        Ok(executable) => executable.file_name() == program,
+        // The executable of processes owned by other users cannot be read.
+        Err(_) => proc.exists(),
+    }
+}
+
+/// Returns true as liveness of the process cannot be verified.
+#[cfg(target_family = "windows")]
+fn is_running(_id: u32, _program: &str) -> bool {
+    true
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -70,6 +186,112 @@ mod tests {
 
         // Assert
         assert_eq!(Some(value), result);
+        std::fs::remove_file(&path).expect("Failed to delete test file.");
+    }
+
+    #[test]
+    fn read_fails_if_file_is_invalid() {
+        // Arrange
+        let path = format!("test-{}.pid", Faker.fake::<String>());
+        std::fs::write(&path, "invalid").expect("Failed to create test file.");
+        let pid = Pid::new(&path);
+
+        // Act
+        let result = pid.read();
+
+        // Assert
+        assert_eq!(
+            std::io::ErrorKind::InvalidData,
+            result.expect_err("Read should fail.").kind()
+        );
+        std::fs::remove_file(&path).expect("Failed to delete test file.");
+    }
+
+    #[test]
+    fn read_reads_none_if_file_is_reset() {
+        // Arrange
+        let path = format!("test-{}.pid", Faker.fake::<String>());
+        std::fs::write(&path, "0").expect("Failed to create test file.");
+        let pid = Pid::new(&path);
+
+        // Act
+        let result = pid.read().expect("Failed to read pid.");
+
+        // Assert
+        assert_eq!(None, result);
+        std::fs::remove_file(&path).expect("Failed to delete test file.");
+    }
+
+    #[test]
+    fn inspect_detects_running_process() {
+        // Arrange
+        let path = format!("test-{}.pid", Faker.fake::<String>());
+        let pid = Pid::new(&path);
+        pid.update(std::process::id())
+            .expect("Failed to update pid.");
+        let program = std::env::current_exe().expect("Failed to get current executable.");
+
+        // Act
+        let result = pid
+            .inspect(program.to_str().unwrap())
+            .expect("Failed to inspect pid.");
+
+        // Assert
+        assert_eq!(Recorded::Running(std::process::id()), result);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn inspect_detects_reused_process_id() {
+        // Arrange
+        let path = format!("test-{}.pid", Faker.fake::<String>());
+        let pid = Pid::new(&path);
+        pid.update(std::process::id())
+            .expect("Failed to update pid.");
+
+        // Act
+        let result = pid.inspect("tor").expect("Failed to inspect pid.");
+
+        // Assert
+        assert_eq!(Recorded::Stale(std::process::id()), result);
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn lock_fails_if_already_locked() {
+        // Arrange
+        let path = format!("test-{}.pid", Faker.fake::<String>());
+        let mut first = Pid::new(&path);
+        let mut second = Pid::new(&path);
+
+        // Act
+        first.lock().expect("Failed to lock pid.");
+        let result = second.lock();
+
+        // Assert
+        assert_eq!(
+            std::io::ErrorKind::WouldBlock,
+            result.expect_err("Lock should fail.").kind()
+        );
+        std::fs::remove_file(format!("{}.lock", path)).expect("Failed to delete test file.");
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn lock_is_released_on_drop() {
+        // Arrange
+        let path = format!("test-{}.pid", Faker.fake::<String>());
+        let mut first = Pid::new(&path);
+        first.lock().expect("Failed to lock pid.");
+
+        // Act
+        drop(first);
+        let result = Pid::new(&path).lock();
+
+        // Assert
+        assert!(result.is_ok());
+        assert!(Path::new(&format!("{}.lock", path)).exists());
+        std::fs::remove_file(format!("{}.lock", path)).expect("Failed to delete test file.");
     }
 
     #[test]
@@ -161,6 +383,8 @@ mod tests {
         // Arrange
         let path = format!("test-{}.pid", Faker.fake::<String>());
         let pid = Pid::new(&path);
+        pid.update(Faker.fake::<u32>())
+            .expect("Failed to update pid.");
 
         // Act
         std::mem::drop(pid);
@@ -168,4 +392,20 @@ mod tests {
         // Assert
         assert_eq!(false, std::path::Path::new(&path).exists())
     }
+
+    #[test]
+    fn drop_keeps_file_if_not_written() {
+        // Arrange
+        let path = format!("test-{}.pid", Faker.fake::<String>());
+        std::fs::write(&path, Faker.fake::<u32>().to_string())
+            .expect("Failed to create test file.");
+        let pid = Pid::new(&path);
+
+        // Act
+        std::mem::drop(pid);
+
+        // Assert
+        assert!(std::path::Path::new(&path).exists());
+        std::fs::remove_file(&path).expect("Failed to delete test file.");
+    }
 }
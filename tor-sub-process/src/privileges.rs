@@ -0,0 +1,298 @@
+use crate::error::Error;
+
+/// Privileges Tor is spawned with, reducing what a compromised Tor can do.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Privileges {
+    /// User and group ids to drop to.
+    pub user: Option<(u32, u32)>,
+    /// Maximum number of open file descriptors, `RLIMIT_NOFILE`.
+    pub open_files: Option<u64>,
+    /// Maximum size of the virtual memory in bytes, `RLIMIT_AS`.
+    pub memory: Option<u64>,
+    /// File mode creation mask, Tor requires hidden service directories to be `0700`.
+    pub umask: Option<u32>,
+    /// Clears the environment inherited from the current process.
+    pub clear_env: bool,
+}
+
+impl Privileges {
+    /// Checks the privileges can be applied to `program`, explaining why if they cannot.
+    #[cfg(target_family = "unix")]
+    pub fn validate(&self, program: &str) -> Result<(), Error> {
+        if let Some((uid, gid)) = self.user {
+            let (euid, egid) = unsafe { (libc::geteuid(), libc::getegid()) };
+            if euid != 0 && (uid != euid || gid != egid) {
+                return Err(Error::InvalidCommand(format!(
+                    "Switching to uid {} and gid {} requires root, running as uid {}.",
+                    uid, gid, euid
+                )));
+            }
+        }
+
+        if let Some(umask) = self.umask {
+            if umask > 0o777 {
+                return Err(Error::InvalidCommand(format!(
+                    "Umask {:o} is not a file mode.",
+                    umask
+                )));
+            }
+            if umask & 0o077 != 0o077 {
+                return Err(Error::InvalidCommand(format!(
+                    "Umask {:03o} allows group or other access, Tor requires hidden service directories to be 0700.",
+                    umask
+                )));
+            }
+        }
+
+        validate_rlimit("open files", libc::RLIMIT_NOFILE, self.open_files)?;
+        validate_rlimit("memory", libc::RLIMIT_AS, self.memory)?;
+
+        if self.clear_env && !std::path::Path::new(program).is_absolute() {
+            return Err(Error::InvalidCommand(format!(
+                "Program '{}' must be an absolute path when the environment is cleared.",
+                program
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Checks the privileges can be applied to `program`, explaining why if they cannot.
+    #[cfg(target_family = "windows")]
+    pub fn validate(&self, _program: &str) -> Result<(), Error> {
+        if self.user.is_some()
+            || self.open_files.is_some()
+            || self.memory.is_some()
+            || self.umask.is_some()
+        {
+            return Err(Error::InvalidCommand(
+                "Users, resource limits and umask are not supported on Windows.".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Applies the privileges to the command, taking effect in the child before Tor is executed.
+    ///
+    /// The user is switched after the resource limits are set, as only root may raise them above
+    /// the hard limit. `Command::uid` cannot be used as it switches before `pre_exec` runs.
+    #[cfg(target_family = "unix")]
+    pub fn apply(&self, command: &mut tokio::process::Command) {
+        if self.clear_env {
+            command.env_clear();
+        }
+
+        let user = self.user;
+        let open_files = self.open_files;
+        let memory = self.memory;
+        let umask = self.umask;
+
+        if user.is_none() && open_files.is_none() && memory.is_none() && umask.is_none() {
+            return;
+        }
+
+        unsafe {
+            command.pre_exec(move || {
+                if let Some(open_files) = open_files {
+                    set_rlimit(libc::RLIMIT_NOFILE, open_files)?;
+                }
+                if let Some(memory) = memory {
+                    set_rlimit(libc::RLIMIT_AS, memory)?;
+                }
+                if let Some(umask) = umask {
+                    libc::umask(umask as libc::mode_t);
+                }
+                if let Some((uid, gid)) = user {
+                    set_user(uid, gid)?;
+                }
+                Ok(())
+            });
+        }
+    }
+
+    /// Applies the privileges to the command.
+    #[cfg(target_family = "windows")]
+    pub fn apply(&self, command: &mut tokio::process::Command) {
+        if self.clear_env {
+            command.env_clear();
+        }
+    }
+}
+
+/// Resource passed to `getrlimit` and `setrlimit`, its type differs between libc implementations.
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+type Resource = libc::__rlimit_resource_t;
+#[cfg(all(
+    target_family = "unix",
+    not(all(target_os = "linux", target_env = "gnu"))
+))]
+type Resource = libc::c_int;
+
+/// Checks a resource limit is positive and, unless running as root, within the hard limit.
+#[cfg(target_family = "unix")]
+fn validate_rlimit(name: &str, resource: Resource, limit: Option<u64>) -> Result<(), Error> {
+    let limit = match limit {
+        Some(limit) => limit,
+        None => return Ok(()),
+    };
+
+    if limit == 0 {
+        return Err(Error::InvalidCommand(format!(
+            "Limit on {} must be greater than zero.",
+            name
+        )));
+    }
+
+    let mut current = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    if unsafe { libc::getrlimit(resource, &mut current) } != 0 {
+        return Err(Error::Io(std::io::Error::last_os_error()));
+    }
+
+    if unsafe { libc::geteuid() } != 0 && limit > current.rlim_max {
+        return Err(Error::InvalidCommand(format!(
+            "Limit on {} of {} exceeds the hard limit of {}.",
+            name, limit, current.rlim_max
+        )));
+    }
+
+    Ok(())
+}
+
+/// Drops the supplementary groups, then switches group and user, as `Command::uid` does.
+#[cfg(target_family = "unix")]
+fn set_user(uid: u32, gid: u32) -> Result<(), std::io::Error> {
+    unsafe {
+        if libc::geteuid() == 0 && libc::setgroups(0, std::ptr::null()) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        if libc::setgid(gid) != 0 || libc::setuid(uid) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Sets the soft and hard limit of a resource.
+#[cfg(target_family = "unix")]
+fn set_rlimit(resource: Resource, limit: u64) -> Result<(), std::io::Error> {
+    let limit = libc::rlimit {
+        rlim_cur: limit,
+        rlim_max: limit,
+    };
+    match unsafe { libc::setrlimit(resource, &limit) } {
+        0 => Ok(()),
+        _ => Err(std::io::Error::last_os_error()),
+    }
+}
+
+#[cfg(target_family = "unix")]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_rejects_permissive_umask() {
+        // Arrange
+        let privileges = Privileges {
+            umask: Some(0o022),
+            ..Privileges::default()
+        };
+
+        // Act
+        let validated = privileges.validate("/usr/bin/tor");
+
+        // Assert
+        assert!(matches!(validated, Err(Error::InvalidCommand(_))));
+    }
+
+    #[test]
+    fn validate_rejects_relative_program_with_cleared_environment() {
+        // Arrange
+        let privileges = Privileges {
+            clear_env: true,
+            ..Privileges::default()
+        };
+
+        // Act
+        let relative = privileges.validate("tor");
+        let absolute = privileges.validate("/usr/bin/tor");
+
+        // Assert
+        assert!(matches!(relative, Err(Error::InvalidCommand(_))));
+        assert!(absolute.is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_zero_limit() {
+        // Arrange
+        let privileges = Privileges {
+            open_files: Some(0),
+            ..Privileges::default()
+        };
+
+        // Act
+        let validated = privileges.validate("/usr/bin/tor");
+
+        // Assert
+        assert!(matches!(validated, Err(Error::InvalidCommand(_))));
+    }
+
+    #[tokio::test]
+    async fn apply_sets_limits_umask_and_environment() {
+        // Arrange
+        let privileges = Privileges {
+            open_files: Some(256),
+            umask: Some(0o077),
+            clear_env: true,
+            ..Privileges::default()
+        };
+        let mut command = tokio::process::Command::new("/bin/sh");
+        command
+            .arg("-c")
+            .arg("umask; ulimit -n; echo \"home=$HOME\"")
+            .stdout(std::process::Stdio::piped());
+
+        // Act
+        privileges.apply(&mut command);
+        let output = command.output().await.expect("Failed to run command.");
+
+        // Assert
+        assert_eq!(
+            "0077\n256\nhome=\n",
+            String::from_utf8_lossy(&output.stdout)
+        );
+    }
+
+    #[tokio::test]
+    async fn apply_sets_limits_then_switches_user() {
+        if unsafe { libc::geteuid() } != 0 {
+            // only root may switch user
+            return;
+        }
+
+        // Arrange
+        let privileges = Privileges {
+            user: Some((65534, 65534)),
+            open_files: Some(256),
+            ..Privileges::default()
+        };
+        let mut command = tokio::process::Command::new("/bin/sh");
+        command
+            .arg("-c")
+            .arg("ulimit -n; id -u; id -G")
+            .stdout(std::process::Stdio::piped());
+
+        // Act
+        privileges.apply(&mut command);
+        let output = command.output().await.expect("Failed to run command.");
+
+        // Assert
+        assert_eq!(
+            "256\n65534\n65534\n",
+            String::from_utf8_lossy(&output.stdout)
+        );
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Determines which exits of a job are followed by a restart.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RestartMode {
+    Always,
+    OnFailure,
+    Never,
+}
+
+/// Decision taken by a `RestartPolicy` after a job exited.
+#[derive(Debug, PartialEq)]
+pub enum Restart {
+    /// Restart the job after the delay.
+    After(Duration),
+    /// Do not restart the job, the exit was expected.
+    Stop,
+    /// Do not restart the job, the job is considered to have failed.
+    Fail,
+}
+
+/// Governs restarts of a job that exited while it was expected to be running.
+#[derive(Clone, Debug)]
+pub struct RestartPolicy {
+    mode: RestartMode,
+    max_restarts: usize,
+    window: Duration,
+    backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl RestartPolicy {
+    /// Constructs a new `RestartPolicy`.
+    ///
+    /// At most `max_restarts` restarts are allowed within `window`, beyond which the job is
+    /// considered to be crash looping. Each restart within the window doubles the delay before
+    /// the job is started again, beginning at `backoff` and capped at `max_backoff`.
+    pub fn new(
+        mode: RestartMode,
+        max_restarts: usize,
+        window: Duration,
+        backoff: Duration,
+        max_backoff: Duration,
+    ) -> Self {
+        Self {
+            mode,
+            max_restarts,
+            window,
+            backoff,
+            max_backoff,
+        }
+    }
+
+    /// Decides whether a job which exited at `now` should be restarted, recording the restart
+    /// in `history`.
+    pub fn decide(&self, history: &mut RestartHistory, success: bool, now: Instant) -> Restart {
+        match (self.mode, success) {
+            (RestartMode::Never, true) | (RestartMode::OnFailure, true) => return Restart::Stop,
+            (RestartMode::Never, false) => return Restart::Fail,
+            _ => {}
+        }
+
+        while let Some(&restart) = history.restarts.front() {
+            if now.duration_since(restart) < self.window {
+                break;
+            }
+            history.restarts.pop_front();
+        }
+
+        if history.restarts.len() >= self.max_restarts {
+            return Restart::Fail;
+        }
+
+        let delay = self
+            .backoff
+            .checked_mul(1 << history.restarts.len().min(31) as u32)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff);
+
+        history.restarts.push_back(now);
+
+        Restart::After(delay)
+    }
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self::new(
+            RestartMode::Always,
+            5,
+            Duration::from_secs(60),
+            Duration::from_secs(1),
+            Duration::from_secs(30),
+        )
+    }
+}
+
+/// Restarts recently performed under a `RestartPolicy`.
+#[derive(Default)]
+pub struct RestartHistory {
+    restarts: VecDeque<Instant>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(mode: RestartMode) -> RestartPolicy {
+        RestartPolicy::new(
+            mode,
+            3,
+            Duration::from_secs(60),
+            Duration::from_secs(1),
+            Duration::from_secs(3),
+        )
+    }
+
+    #[test]
+    fn decide_follows_mode() {
+        let now = Instant::now();
+
+        let always = policy(RestartMode::Always);
+        assert_eq!(
+            Restart::After(Duration::from_secs(1)),
+            always.decide(&mut RestartHistory::default(), true, now)
+        );
+        assert_eq!(
+            Restart::After(Duration::from_secs(1)),
+            always.decide(&mut RestartHistory::default(), false, now)
+        );
+
+        let on_failure = policy(RestartMode::OnFailure);
+        assert_eq!(
+            Restart::Stop,
+            on_failure.decide(&mut RestartHistory::default(), true, now)
+        );
+        assert_eq!(
+            Restart::After(Duration::from_secs(1)),
+            on_failure.decide(&mut RestartHistory::default(), false, now)
+        );
+
+        let never = policy(RestartMode::Never);
+        assert_eq!(
+            Restart::Stop,
+            never.decide(&mut RestartHistory::default(), true, now)
+        );
+        assert_eq!(
+            Restart::Fail,
+            never.decide(&mut RestartHistory::default(), false, now)
+        );
+    }
+
+    #[test]
+    fn decide_backs_off_then_fails_when_crash_looping() {
+        let policy = policy(RestartMode::Always);
+        let mut history = RestartHistory::default();
+        let now = Instant::now();
+
+        assert_eq!(
+            Restart::After(Duration::from_secs(1)),
+            policy.decide(&mut history, false, now)
+        );
+        assert_eq!(
+            Restart::After(Duration::from_secs(2)),
+            policy.decide(&mut history, false, now)
+        );
+        assert_eq!(
+            Restart::After(Duration::from_secs(3)),
+            policy.decide(&mut history, false, now)
+        );
+        assert_eq!(Restart::Fail, policy.decide(&mut history, false, now));
+    }
+
+    #[test]
+    fn decide_forgets_restarts_outside_window() {
+        let policy = policy(RestartMode::Always);
+        let mut history = RestartHistory::default();
+        let now = Instant::now();
+
+        policy.decide(&mut history, false, now);
+        policy.decide(&mut history, false, now);
+        policy.decide(&mut history, false, now);
+
+        assert_eq!(
+            Restart::After(Duration::from_secs(1)),
+            policy.decide(&mut history, false, now + Duration::from_secs(60))
+        );
+    }
+}
@@ -1,71 +1,337 @@
 use crate::command::Command;
-use crate::event_loop;
-use crate::pid::Pid;
-use std::sync::atomic::{AtomicBool, Ordering};
+use crate::error::Error;
+use crate::event_loop::{self, Request};
+use crate::lifecycle::{Event, Lifecycle};
+use crate::orphan::{self, OrphanPolicy};
+use crate::pid::{Pid, Recorded};
+use crate::restart_policy::RestartPolicy;
+use crate::status::{State, Status};
+use crate::stop_policy::{StopPolicy, Termination};
+use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, oneshot, watch};
 use tokio::task::JoinHandle;
 
+/// Reload queued with the event loop, acknowledged once the reload including it completes.
+pub struct PendingReload {
+    acknowledged: oneshot::Receiver<Result<u64, Error>>,
+}
+
+impl PendingReload {
+    /// Waits for the reload, returning the generation which included the request.
+    pub async fn wait(self) -> Result<u64, Error> {
+        self.acknowledged.await.map_err(|_| Error::NotRunning)?
+    }
+}
+
 /// Represents a long running job lifecycle.
 pub struct Scheduler {
     command: Command,
-    handle: Option<JoinHandle<()>>,
+    handle: Option<JoinHandle<Result<Option<Termination>, Error>>>,
     pid: String,
-    reload: Arc<AtomicBool>,
-    terminate: Arc<AtomicBool>,
+    restart_policy: RestartPolicy,
+    stop_policy: StopPolicy,
+    orphan_policy: OrphanPolicy,
+    reload_debounce: Duration,
+    status: watch::Receiver<Status>,
+    statuses: Arc<watch::Sender<Status>>,
+    events: broadcast::Sender<Event>,
+    requests: Option<mpsc::Sender<Request>>,
 }
 
 impl Scheduler {
-    pub fn new(command: Command, pid: &str) -> Self {
+    pub fn new(
+        command: Command,
+        pid: &str,
+        restart_policy: RestartPolicy,
+        stop_policy: StopPolicy,
+        orphan_policy: OrphanPolicy,
+        reload_debounce: Duration,
+    ) -> Self {
+        let (statuses, status) = watch::channel(Status::default());
+        let (events, _) = broadcast::channel(64);
         Self {
             command,
             handle: None,
             pid: pid.to_string(),
-            reload: Arc::new(AtomicBool::new(false)),
-            terminate: Arc::new(AtomicBool::new(false)),
+            restart_policy,
+            stop_policy,
+            orphan_policy,
+            reload_debounce,
+            status,
+            statuses: Arc::new(statuses),
+            events,
+            requests: None,
         }
     }
 
-    /// Starts the scheduler event loop.
-    pub fn start(&mut self) {
-        let pid = Pid::new(&self.pid);
+    /// Starts the scheduler event loop, handling any process left running by an unexpected
+    /// shutdown according to the orphan policy.
+    pub async fn start(&mut self) -> Result<(), Error> {
+        match &self.handle {
+            // The event loop ends on its own once the restart policy gives up on the job.
+            Some(handle) if handle.is_finished() => {
+                self.handle = None;
+                self.requests = None;
+            }
+            Some(_) => return Err(Error::AlreadyRunning),
+            None => {}
+        }
+
+        self.command.validate()?;
 
-        if let Some(id) = pid.read().expect("Failed to read PID.") {
-            panic!(
-                "Failed to start scheduler. Potential zombie process found. This is caused by an unexpected shutdown. Please check PID `{}` is no longer running then delete 'job.pid'.",
-                id
-            )
+        let mut pid = Pid::new(&self.pid);
+        pid.lock().map_err(Error::Pid)?;
+
+        match pid.inspect(self.command.program()).map_err(Error::Pid)? {
+            Recorded::None => {}
+            Recorded::Stale(id) => {
+                tracing::warn!(pid = id, path = %self.pid, "ignoring stale PID file");
+            }
+            Recorded::Running(id) => match self.orphan_policy {
+                OrphanPolicy::Refuse => {
+                    return Err(Error::Pid(std::io::Error::new(
+                        std::io::ErrorKind::AlreadyExists,
+                        format!(
+                            "Orphaned process found. This is caused by an unexpected shutdown. Please stop PID `{}` then delete '{}'.",
+                            id, self.pid
+                        ),
+                    )));
+                }
+                OrphanPolicy::Reap => {
+                    let escalation = orphan::reap(id, &self.stop_policy).await?;
+                    tracing::warn!(pid = id, ?escalation, "reaped orphaned process");
+                }
+            },
         }
 
+        let (sender, receiver) = mpsc::channel(16);
         let task = event_loop::event_loop(
             self.command.clone(),
             pid,
-            self.reload.clone(),
-            self.terminate.clone(),
+            self.restart_policy.clone(),
+            self.stop_policy.clone(),
+            self.reload_debounce,
+            Lifecycle::new(self.statuses.clone(), self.events.clone()),
+            receiver,
         );
         let handle = tokio::spawn(task);
         self.handle = Some(handle);
+        self.requests = Some(sender);
+        Ok(())
     }
 
-    /// Waits for scheduler to exit completely.
-    pub async fn stop(&mut self) -> Result<(), std::io::Error> {
-        if let Some(handle) = self.handle.take() {
-            self.terminate.swap(true, Ordering::Relaxed);
-            let _ = handle.await?;
-            return Ok(());
+    /// Waits for scheduler to exit completely, returning how the job was stopped if it was
+    /// running.
+    pub async fn stop(&mut self) -> Result<Option<Termination>, Error> {
+        let handle = self.handle.take().ok_or(Error::NotRunning)?;
+
+        if let Some(requests) = self.requests.take() {
+            // The event loop may have already exited, in which case its outcome is in the handle.
+            let _ = requests.send(Request::Stop).await;
         }
 
-        unimplemented!();
+        handle.await.map_err(|error| Error::Io(error.into()))?
     }
 
-    /// Triggers a reload of the job.
-    ///  * Unix: sends reload signal.
+    /// Verifies the torrc then triggers a reload of the job, waiting for it to be acknowledged
+    /// with the generation of the reload which included it.
+    ///
+    /// Reloads requested within the debounce window are coalesced into a single reload.
+    ///  * Unix: sends reload signal, failing if the job exits before the signal is sent.
     ///  * Windows: recreates the job.
-    pub fn reload(&mut self) {
-        if self.handle.is_some() {
-            self.reload.swap(true, Ordering::Relaxed);
-            return;
+    pub async fn reload(&mut self) -> Result<u64, Error> {
+        self.request(|ack| Request::Reload(None, ack)).await
+    }
+
+    /// Verifies the `candidate` torrc, swapping it in and reloading the job only if Tor accepts
+    /// it, waiting for it to be acknowledged with the generation of the reload which included it.
+    ///
+    /// The latest candidate requested within the debounce window supersedes earlier ones, whose
+    /// requests are acknowledged with the outcome of the reload of the latest candidate.
+    pub async fn reload_config(&mut self, candidate: &Path) -> Result<u64, Error> {
+        self.queue_reload_config(candidate).await?.wait().await
+    }
+
+    /// Queues a reload of the `candidate` torrc without waiting for it, so the caller need not
+    /// hold on to the scheduler while the reload is debounced.
+    pub async fn queue_reload_config(&self, candidate: &Path) -> Result<PendingReload, Error> {
+        let candidate = candidate.to_path_buf();
+        let acknowledged = self
+            .send(|ack| Request::Reload(Some(candidate), ack))
+            .await?;
+        Ok(PendingReload { acknowledged })
+    }
+
+    /// Replaces the command and recreates the job, waiting for it to be acknowledged.
+    pub async fn reconfigure(&mut self, command: Command) -> Result<(), Error> {
+        command.validate()?;
+        self.command = command.clone();
+
+        match self.handle {
+            Some(_) => {
+                self.request(|ack| Request::Reconfigure(Box::new(command), ack))
+                    .await
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Changes the window over which reloads are coalesced, taking effect from the next reload
+    /// requested.
+    pub async fn set_reload_debounce(&mut self, reload_debounce: Duration) -> Result<(), Error> {
+        self.reload_debounce = reload_debounce;
+
+        match self.handle {
+            Some(_) => {
+                self.request(|ack| Request::Debounce(reload_debounce, ack))
+                    .await
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Returns a snapshot of the job status.
+    pub fn status(&self) -> Status {
+        self.status.borrow().clone()
+    }
+
+    /// Returns the lifecycle state of the job.
+    pub fn state(&self) -> State {
+        self.status.borrow().state
+    }
+
+    /// Returns a receiver notified of every change to the job status.
+    pub fn watch(&self) -> watch::Receiver<Status> {
+        self.status.clone()
+    }
+
+    /// Returns a receiver of the lifecycle events published from now on.
+    pub fn events(&self) -> broadcast::Receiver<Event> {
+        self.events.subscribe()
+    }
+
+    /// Sends a request to the event loop and waits for it to be acknowledged.
+    async fn request<T>(
+        &self,
+        request: impl FnOnce(oneshot::Sender<Result<T, Error>>) -> Request,
+    ) -> Result<T, Error> {
+        let acknowledged = self.send(request).await?;
+        acknowledged.await.map_err(|_| Error::NotRunning)?
+    }
+
+    /// Sends a request to the event loop, returning the receiver of its acknowledgement.
+    async fn send<T>(
+        &self,
+        request: impl FnOnce(oneshot::Sender<Result<T, Error>>) -> Request,
+    ) -> Result<oneshot::Receiver<Result<T, Error>>, Error> {
+        let requests = self.requests.as_ref().ok_or(Error::NotRunning)?;
+        let (ack, acknowledged) = oneshot::channel();
+        requests
+            .send(request(ack))
+            .await
+            .map_err(|_| Error::NotRunning)?;
+        Ok(acknowledged)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::restart_policy::RestartMode;
+    use fake::{Fake, Faker};
+
+    #[tokio::test]
+    async fn it_rejects_requests_if_not_started() {
+        // Arrange
+        let path = format!("test-{}.pid", Faker.fake::<String>());
+        let mut scheduler = Scheduler::new(
+            Command::new("tor", false),
+            &path,
+            RestartPolicy::default(),
+            StopPolicy::default(),
+            OrphanPolicy::default(),
+            Duration::from_millis(0),
+        );
+
+        // Act
+        let reloaded = scheduler.reload().await;
+        let queued = scheduler.queue_reload_config(Path::new("torrc")).await;
+        let stopped = scheduler.stop().await;
+
+        // Assert
+        assert!(matches!(reloaded, Err(Error::NotRunning)));
+        assert!(matches!(queued, Err(Error::NotRunning)));
+        assert!(matches!(stopped, Err(Error::NotRunning)));
+        assert_eq!(State::Pending, scheduler.state());
+    }
+
+    #[tokio::test]
+    async fn it_refuses_to_start_if_orphan_is_running() {
+        // Arrange
+        let path = format!("test-{}.pid", Faker.fake::<String>());
+        std::fs::write(&path, std::process::id().to_string()).expect("Failed to create test file.");
+        let program = std::env::current_exe().expect("Failed to get current executable.");
+        let mut scheduler = Scheduler::new(
+            Command::new(program.to_str().unwrap(), false),
+            &path,
+            RestartPolicy::default(),
+            StopPolicy::default(),
+            OrphanPolicy::Refuse,
+            Duration::from_millis(0),
+        );
+
+        // Act
+        let started = scheduler.start().await;
+
+        // Assert
+        assert!(matches!(started, Err(Error::Pid(_))));
+        assert!(std::path::Path::new(&path).exists());
+        std::fs::remove_file(&path).expect("Failed to delete test file.");
+        std::fs::remove_file(format!("{}.lock", path)).expect("Failed to delete test file.");
+    }
+
+    #[tokio::test]
+    async fn it_starts_again_after_failing() {
+        // Arrange
+        let path = format!("test-{}.pid", Faker.fake::<String>());
+        let program = std::env::current_dir()
+            .unwrap()
+            .join("../target/debug/tor-stub");
+        let mut command = Command::new(program.to_str().unwrap(), false);
+        command
+            .arg("--no-wait")
+            .arg("--exit-after")
+            .arg("0.1")
+            .arg("--exit-code")
+            .arg("3");
+        let mut scheduler = Scheduler::new(
+            command,
+            &path,
+            RestartPolicy::new(
+                RestartMode::Never,
+                0,
+                Duration::from_secs(60),
+                Duration::from_millis(10),
+                Duration::from_millis(10),
+            ),
+            StopPolicy::default(),
+            OrphanPolicy::default(),
+            Duration::from_millis(0),
+        );
+        scheduler.start().await.expect("Failed to start.");
+        while !scheduler.handle.as_ref().unwrap().is_finished() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
         }
+        assert_eq!(State::Failed, scheduler.state());
+
+        // Act
+        let started = scheduler.start().await;
 
-        unimplemented!();
+        // Assert
+        assert!(started.is_ok());
+        scheduler.stop().await.expect("Failed to stop.");
+        std::fs::remove_file(format!("{}.lock", path)).expect("Failed to delete test file.");
     }
 }
@@ -0,0 +1,75 @@
+//! Safe wrappers for libc interfaces
+
+use crate::stop_policy::Escalation;
+
+#[cfg(target_family = "unix")]
+pub fn sighup(id: u32) -> Result<(), std::io::Error> {
+    kill(id, signal_hook::consts::signal::SIGHUP)
+}
+
+/// Sends the signal of the escalation to the process, or to every process in the process group
+/// it leads.
+#[cfg(target_family = "unix")]
+pub fn escalate(id: u32, escalation: Escalation, group: bool) -> Result<(), std::io::Error> {
+    let signal = match escalation {
+        Escalation::Sigterm => signal_hook::consts::signal::SIGTERM,
+        Escalation::Sigint => signal_hook::consts::signal::SIGINT,
+        Escalation::Sigkill => signal_hook::consts::signal::SIGKILL,
+    };
+
+    match group {
+        true => killpg(id, signal),
+        false => kill(id, signal),
+    }
+}
+
+/// Returns true if a process with the id exists.
+#[cfg(target_family = "unix")]
+pub fn exists(id: u32) -> bool {
+    match kill(id, 0) {
+        Ok(()) => true,
+        Err(error) => error.raw_os_error() == Some(libc::EPERM),
+    }
+}
+
+#[cfg(target_family = "unix")]
+fn kill(id: u32, signal: i32) -> Result<(), std::io::Error> {
+    match unsafe { libc::kill(id as i32, signal) } {
+        0 => Ok(()),
+        _ => Err(std::io::Error::last_os_error()),
+    }
+}
+
+#[cfg(target_family = "unix")]
+fn killpg(id: u32, signal: i32) -> Result<(), std::io::Error> {
+    match unsafe { libc::killpg(id as i32, signal) } {
+        0 => Ok(()),
+        _ => Err(std::io::Error::last_os_error()),
+    }
+}
+
+/// Sends the signal of the escalation to the process, SIGKILL is left to `Child::kill`.
+#[cfg(target_family = "windows")]
+pub fn escalate(id: u32, escalation: Escalation, _group: bool) -> Result<(), std::io::Error> {
+    match escalation {
+        Escalation::Sigterm => sigterm(id),
+        Escalation::Sigint => sigint(id),
+        Escalation::Sigkill => Ok(()),
+    }
+}
+
+#[cfg(target_family = "windows")]
+fn sigterm(id: u32) -> Result<(), std::io::Error> {
+    unsafe {
+        libc::signal(signal_hook::consts::signal::SIGTERM, id as usize);
+    }
+    Ok(())
+}
+
+#[cfg(target_family = "windows")]
+fn sigint(id: u32) -> Result<(), std::io::Error> {
+    unsafe {
+        libc::signal(signal_hook::consts::signal::SIGINT, id as usize);
+    }
+    Ok(())
+}
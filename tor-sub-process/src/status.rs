@@ -0,0 +1,42 @@
+/// Lifecycle state of a scheduled job.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum State {
+    /// The scheduler has not started the job yet.
+    Pending,
+    /// The job is being started.
+    Starting,
+    /// The job is running.
+    Running,
+    /// The job is being reloaded.
+    Reloading,
+    /// The job exited and is waiting to be restarted.
+    Restarting,
+    /// The job exited and will not be restarted as its restart policy has been exhausted.
+    Failed,
+    /// The job has been stopped.
+    Stopped,
+}
+
+/// Snapshot of a scheduled job.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Status {
+    pub state: State,
+    /// Number of restarts attempted.
+    pub restarts: u32,
+    /// Exit code of the last run of the job, `None` if it has not exited or was terminated by
+    /// a signal.
+    pub last_exit_code: Option<i32>,
+    /// Last lines of output of the last run of the job which exited.
+    pub last_output: Vec<String>,
+}
+
+impl Default for Status {
+    fn default() -> Self {
+        Self {
+            state: State::Pending,
+            restarts: 0,
+            last_exit_code: None,
+            last_output: Vec::new(),
+        }
+    }
+}
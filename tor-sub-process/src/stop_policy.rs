@@ -0,0 +1,67 @@
+use std::process::Output;
+use std::time::Duration;
+
+/// Governs how a job is stopped, escalating when it does not exit in time.
+///
+/// The job is first sent SIGTERM. If it is still running after `terminate_timeout` it is sent
+/// SIGINT, which Tor treats as a graceful shutdown honouring `ShutdownWaitLength`. If it is still
+/// running after `interrupt_timeout` it is sent SIGKILL.
+#[derive(Clone, Debug)]
+pub struct StopPolicy {
+    terminate_timeout: Duration,
+    interrupt_timeout: Duration,
+    process_group: bool,
+}
+
+impl StopPolicy {
+    pub fn new(terminate_timeout: Duration, interrupt_timeout: Duration) -> Self {
+        Self {
+            terminate_timeout,
+            interrupt_timeout,
+            process_group: false,
+        }
+    }
+
+    /// Signals the whole process group led by the job instead of only the job, stopping any
+    /// processes it spawned. Unix only.
+    pub fn with_process_group(mut self, process_group: bool) -> Self {
+        self.process_group = process_group;
+        self
+    }
+
+    /// Time to wait for the job to exit after SIGTERM.
+    pub fn terminate_timeout(&self) -> Duration {
+        self.terminate_timeout
+    }
+
+    /// Time to wait for the job to exit after SIGINT.
+    pub fn interrupt_timeout(&self) -> Duration {
+        self.interrupt_timeout
+    }
+
+    /// Whether the whole process group led by the job is signalled.
+    pub fn process_group(&self) -> bool {
+        self.process_group
+    }
+}
+
+impl Default for StopPolicy {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(10), Duration::from_secs(30))
+    }
+}
+
+/// Last signal sent to a job before it exited.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Escalation {
+    Sigterm,
+    Sigint,
+    Sigkill,
+}
+
+/// Outcome of stopping a job.
+#[derive(Clone, Debug)]
+pub struct Termination {
+    pub escalation: Escalation,
+    pub output: Output,
+}
@@ -0,0 +1,135 @@
+use crate::output_buffer::OutputBuffer;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+
+/// Severity of a Tor log line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    Debug,
+    Info,
+    Notice,
+    Warn,
+    Err,
+}
+
+impl Severity {
+    /// Name of the severity as used in Tor configuration.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Debug => "debug",
+            Severity::Info => "info",
+            Severity::Notice => "notice",
+            Severity::Warn => "warn",
+            Severity::Err => "err",
+        }
+    }
+}
+
+/// Forwards each line read from `reader` as a `tracing` event under the `tor` target, keeping the
+/// most recent lines in `buffer`. Invalid UTF-8 is replaced rather than ending the forwarding.
+pub async fn forward(
+    reader: impl AsyncRead + Unpin,
+    pid: u32,
+    default: Severity,
+    buffer: OutputBuffer,
+) {
+    let mut reader = BufReader::new(reader);
+    let mut bytes = Vec::new();
+
+    while let Ok(read) = reader.read_until(b'\n', &mut bytes).await {
+        if read == 0 {
+            break;
+        }
+        let line = String::from_utf8_lossy(&bytes).into_owned();
+        bytes.clear();
+
+        let (severity, message) = parse(&line).unwrap_or((default, line.trim()));
+
+        if message.is_empty() {
+            continue;
+        }
+
+        buffer.push(line.trim());
+
+        emit(severity, pid, message);
+    }
+}
+
+/// Emits a `tracing` event with the level matching the Tor severity.
+fn emit(severity: Severity, pid: u32, message: &str) {
+    match severity {
+        Severity::Debug => tracing::trace!(target: "tor", pid, "{}", message),
+        Severity::Info => tracing::debug!(target: "tor", pid, "{}", message),
+        Severity::Notice => tracing::info!(target: "tor", pid, "{}", message),
+        Severity::Warn => tracing::warn!(target: "tor", pid, "{}", message),
+        Severity::Err => tracing::error!(target: "tor", pid, "{}", message),
+    }
+}
+
+/// Parses a Tor log line of the form `Mar 01 12:00:00.000 [notice] message`.
+fn parse(line: &str) -> Option<(Severity, &str)> {
+    let start = line.find('[')?;
+    let end = start + line[start..].find(']')?;
+
+    let severity = match &line[start + 1..end] {
+        "debug" => Severity::Debug,
+        "info" => Severity::Info,
+        "notice" => Severity::Notice,
+        "warn" => Severity::Warn,
+        "err" => Severity::Err,
+        _ => return None,
+    };
+
+    Some((severity, line[end + 1..].trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_parses_severity_and_message() {
+        assert_eq!(
+            Some((Severity::Notice, "Bootstrapped 100% (done): Done")),
+            parse("Mar 01 12:00:00.000 [notice] Bootstrapped 100% (done): Done")
+        );
+        assert_eq!(
+            Some((Severity::Warn, "Could not bind to 127.0.0.1:9050")),
+            parse("Mar 01 12:00:00.000 [warn] Could not bind to 127.0.0.1:9050")
+        );
+        assert_eq!(
+            Some((Severity::Err, "Reading config failed")),
+            parse("Mar 01 12:00:00.000 [err] Reading config failed")
+        );
+        assert_eq!(
+            Some((Severity::Info, "Loaded")),
+            parse("Mar 01 12:00:00.000 [info] Loaded")
+        );
+        assert_eq!(
+            Some((Severity::Debug, "Polling")),
+            parse("Mar 01 12:00:00.000 [debug] Polling")
+        );
+    }
+
+    #[test]
+    fn parse_returns_none_for_unstructured_lines() {
+        assert_eq!(None, parse("Processing."));
+        assert_eq!(None, parse("Mar 01 12:00:00.000 [unknown] message"));
+        assert_eq!(None, parse("Mar 01 12:00:00.000 [notice message"));
+    }
+
+    #[tokio::test]
+    async fn forward_replaces_invalid_utf8() {
+        let buffer = OutputBuffer::new(10);
+        let output: &[u8] = b"\xff\xfe garbage\nMar 01 12:00:00.000 [notice] Done\n";
+
+        forward(output, 0, Severity::Notice, buffer.clone()).await;
+
+        assert_eq!(
+            vec![
+                "\u{fffd}\u{fffd} garbage".to_string(),
+                "Mar 01 12:00:00.000 [notice] Done".to_string()
+            ],
+            buffer.lines()
+        );
+    }
+}
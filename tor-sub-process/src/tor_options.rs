@@ -0,0 +1,120 @@
+use crate::tor_log::Severity;
+use std::path::PathBuf;
+
+/// Where Tor listens for controller connections.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ControlPort {
+    /// Listens on a TCP port bound to localhost.
+    Port(u16),
+    /// Listens on a Unix domain socket.
+    Socket(PathBuf),
+}
+
+/// Typed Tor options rendered into command line arguments.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TorOptions {
+    pub torrc: Option<PathBuf>,
+    pub data_directory: Option<PathBuf>,
+    pub control_port: Option<ControlPort>,
+    pub cookie_authentication: Option<bool>,
+    pub socks_port: Option<u16>,
+    pub log_level: Option<Severity>,
+}
+
+impl TorOptions {
+    /// Renders the options as Tor command line arguments.
+    pub fn render(&self) -> Vec<String> {
+        let mut args = Vec::new();
+
+        if let Some(torrc) = &self.torrc {
+            args.push("-f".to_string());
+            args.push(torrc.display().to_string());
+        }
+
+        if let Some(data_directory) = &self.data_directory {
+            args.push("--DataDirectory".to_string());
+            args.push(data_directory.display().to_string());
+        }
+
+        match &self.control_port {
+            Some(ControlPort::Port(port)) => {
+                args.push("--ControlPort".to_string());
+                args.push(port.to_string());
+            }
+            Some(ControlPort::Socket(path)) => {
+                args.push("--ControlSocket".to_string());
+                args.push(path.display().to_string());
+            }
+            None => {}
+        }
+
+        if let Some(cookie_authentication) = self.cookie_authentication {
+            args.push("--CookieAuthentication".to_string());
+            args.push(if cookie_authentication { "1" } else { "0" }.to_string());
+        }
+
+        if let Some(socks_port) = self.socks_port {
+            args.push("--SocksPort".to_string());
+            args.push(socks_port.to_string());
+        }
+
+        if let Some(log_level) = self.log_level {
+            args.push("--Log".to_string());
+            args.push(format!("{} stdout", log_level.as_str()));
+        }
+
+        args
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_is_empty_by_default() {
+        // Arrange
+        let options = TorOptions::default();
+
+        // Act
+        let args = options.render();
+
+        // Assert
+        assert!(args.is_empty());
+    }
+
+    #[test]
+    fn render_all_options() {
+        // Arrange
+        let options = TorOptions {
+            torrc: Some(PathBuf::from("torrc")),
+            data_directory: Some(PathBuf::from("data")),
+            control_port: Some(ControlPort::Socket(PathBuf::from("control.sock"))),
+            cookie_authentication: Some(true),
+            socks_port: Some(9050),
+            log_level: Some(Severity::Notice),
+        };
+
+        // Act
+        let args = options.render();
+
+        // Assert
+        assert_eq!(
+            vec![
+                "-f",
+                "torrc",
+                "--DataDirectory",
+                "data",
+                "--ControlSocket",
+                "control.sock",
+                "--CookieAuthentication",
+                "1",
+                "--SocksPort",
+                "9050",
+                "--Log",
+                "notice stdout"
+            ],
+            args
+        );
+    }
+}
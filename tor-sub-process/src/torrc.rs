@@ -0,0 +1,208 @@
+use crate::command::Command;
+use crate::error::Error;
+use std::path::{Path, PathBuf};
+
+/// Runs `tor --verify-config` against `torrc`, returning Tor's diagnostic output if it is rejected.
+pub async fn verify(command: &Command, torrc: &Path) -> Result<(), Error> {
+    let output = command
+        .verify_config(torrc)
+        .output()
+        .await
+        .map_err(Error::Spawn)?;
+
+    if output.status.success() {
+        return Ok(());
+    }
+
+    let mut diagnostic = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !stderr.trim().is_empty() {
+        if !diagnostic.is_empty() {
+            diagnostic.push('\n');
+        }
+        diagnostic.push_str(stderr.trim());
+    }
+
+    Err(Error::InvalidConfig(diagnostic))
+}
+
+/// Last known good torrc, kept so a replaced torrc can be restored.
+pub struct Backup {
+    path: PathBuf,
+    contents: Option<Vec<u8>>,
+}
+
+impl Backup {
+    /// Restores the torrc to its last known good contents.
+    pub fn restore(self) -> Result<(), Error> {
+        match self.contents {
+            Some(contents) => write(&self.path, &contents),
+            None => match std::fs::remove_file(&self.path) {
+                Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(error.into()),
+                _ => Ok(()),
+            },
+        }
+    }
+}
+
+/// Atomically replaces `torrc` with the contents of `candidate`, returning a backup of the
+/// replaced torrc.
+pub fn replace(candidate: &Path, torrc: &Path) -> Result<Backup, Error> {
+    let contents = match std::fs::read(torrc) {
+        Ok(contents) => Some(contents),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => None,
+        Err(error) => return Err(error.into()),
+    };
+
+    write(torrc, &std::fs::read(candidate)?)?;
+
+    Ok(Backup {
+        path: torrc.to_path_buf(),
+        contents,
+    })
+}
+
+/// Copy of a candidate torrc which is verified and swapped in, deleted once dropped.
+pub struct Snapshot {
+    path: PathBuf,
+}
+
+impl Snapshot {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for Snapshot {
+    fn drop(&mut self) {
+        if let Err(error) = std::fs::remove_file(&self.path) {
+            tracing::warn!(%error, path = %self.path.display(), "failed to delete torrc snapshot");
+        }
+    }
+}
+
+/// Copies `candidate` next to `torrc`, returning the copy which is verified and swapped in.
+pub fn snapshot(candidate: &Path, torrc: &Path) -> Result<Snapshot, Error> {
+    let mut path = torrc.as_os_str().to_owned();
+    path.push(".verifying");
+    let path = PathBuf::from(path);
+    write(&path, &std::fs::read(candidate)?)?;
+    Ok(Snapshot { path })
+}
+
+/// Writes `contents` to a temporary file then renames it over `path`.
+fn write(path: &Path, contents: &[u8]) -> Result<(), Error> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    std::fs::write(&temporary, contents)?;
+    std::fs::rename(&temporary, path)?;
+    Ok(())
+}
+
+#[cfg(target_family = "unix")]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fake::{Fake, Faker};
+    use std::os::unix::fs::PermissionsExt;
+
+    #[tokio::test]
+    async fn verify_returns_diagnostic_output_when_rejected() {
+        // Arrange
+        let (program, torrc) = create_files("Invalid");
+
+        // Act
+        let verified = verify(&Command::new(&program, false), Path::new(&torrc)).await;
+
+        // Assert
+        match verified {
+            Err(Error::InvalidConfig(output)) => {
+                assert_eq!("[warn] Failed to parse/validate config", output)
+            }
+            _ => panic!("Expected configuration to be rejected."),
+        }
+        std::fs::remove_file(&program).expect("Failed to delete test file.");
+        std::fs::remove_file(&torrc).expect("Failed to delete test file.");
+    }
+
+    #[tokio::test]
+    async fn verify_accepts_valid_config() {
+        // Arrange
+        let (program, torrc) = create_files("SocksPort 9050");
+
+        // Act
+        let verified = verify(&Command::new(&program, false), Path::new(&torrc)).await;
+
+        // Assert
+        assert!(verified.is_ok());
+        std::fs::remove_file(&program).expect("Failed to delete test file.");
+        std::fs::remove_file(&torrc).expect("Failed to delete test file.");
+    }
+
+    #[test]
+    fn replace_can_be_restored() {
+        // Arrange
+        let torrc = format!("test-{}.torrc", Faker.fake::<String>());
+        let candidate = format!("test-{}.torrc", Faker.fake::<String>());
+        std::fs::write(&torrc, "SocksPort 9050").expect("Failed to create test file.");
+        std::fs::write(&candidate, "SocksPort 9150").expect("Failed to create test file.");
+
+        // Act
+        let backup = replace(Path::new(&candidate), Path::new(&torrc)).expect("Failed to replace.");
+        let replaced = std::fs::read_to_string(&torrc).unwrap();
+        backup.restore().expect("Failed to restore.");
+        let restored = std::fs::read_to_string(&torrc).unwrap();
+
+        // Assert
+        assert_eq!("SocksPort 9150", replaced);
+        assert_eq!("SocksPort 9050", restored);
+        std::fs::remove_file(&torrc).expect("Failed to delete test file.");
+        std::fs::remove_file(&candidate).expect("Failed to delete test file.");
+    }
+
+    #[test]
+    fn snapshot_is_unaffected_by_later_candidates() {
+        // Arrange
+        let torrc = format!("test-{}.torrc", Faker.fake::<String>());
+        let candidate = format!("test-{}.torrc", Faker.fake::<String>());
+        std::fs::write(&candidate, "SocksPort 9150").expect("Failed to create test file.");
+
+        // Act
+        let snapshot = snapshot(Path::new(&candidate), Path::new(&torrc)).expect("Failed to copy.");
+        std::fs::write(&candidate, "SocksPort 9250").expect("Failed to update test file.");
+        let contents = std::fs::read_to_string(snapshot.path()).unwrap();
+        let path = snapshot.path().to_path_buf();
+        drop(snapshot);
+
+        // Assert
+        assert_eq!(PathBuf::from(format!("{}.verifying", torrc)), path);
+        assert_eq!("SocksPort 9150", contents);
+        assert!(!path.exists());
+        std::fs::remove_file(&candidate).expect("Failed to delete test file.");
+    }
+
+    /// Creates a fake Tor which rejects any torrc containing `Invalid`, and a torrc.
+    fn create_files(torrc_contents: &str) -> (String, String) {
+        let program = std::env::current_dir()
+            .unwrap()
+            .join(format!("test-{}.sh", Faker.fake::<String>()))
+            .to_str()
+            .unwrap()
+            .to_string();
+        let torrc = format!("test-{}.torrc", Faker.fake::<String>());
+
+        std::fs::write(
+            &program,
+            "#!/bin/sh\n\
+             [ \"$1\" = \"--verify-config\" ] || exit 2\n\
+             if grep -q Invalid \"$3\"; then echo '[warn] Failed to parse/validate config'; exit 1; fi\n\
+             echo 'Configuration was valid'\n",
+        )
+        .expect("Failed to create test file.");
+        std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o755))
+            .expect("Failed to set permissions.");
+        std::fs::write(&torrc, torrc_contents).expect("Failed to create test file.");
+
+        (program, torrc)
+    }
+}